@@ -13,23 +13,32 @@ use crossterm::{
     },
 };
 use std::{
+    env::args,
     io::{self, Read, stdout},
     time::Duration,
 };
 use track_payments_rust::{
-    payments::{AllPayments, PaymentId},
-    renderer::{render_lines, tui_::bar_graph_horizontal_label},
+    payments::{AllPayments, BudgetScope, ForecastMethod, GroupBy},
+    rates::ExchangeRates,
+    renderer::{render_lines, tui_::bar_graph_horizontal_label_band},
+    time::{FakeUtcTime, Period},
 };
 
 fn main() -> io::Result<()> {
-    let mut data = vec![
+    let mut data: Vec<i64> = vec![
         0, 752, 707, 2787, 1019, 864, 890, 2853, 0, 0, 841, 989, 678, 990, 1812, 0, 733, 714, 782,
         931, 1722, 1803, 862, 1278, 1079, 857, 558, 1450, 536, 857, 649,
     ];
-    let mut ignore = vec![];
+    let ignore = vec![];
+    let mut projected = vec![];
+    let mut band = vec![];
+    let mut currency = String::from("€");
+    let mut cutout = 30_000.0 / data.len() as f64;
 
     if atty::isnt(Stream::Stdin) {
-        let now = Utc::now();
+        // one clock for the month, the forecast and the budgets
+        let today = FakeUtcTime::now();
+        let now = today.get_fields().unwrap();
         let start_of_month = Utc.ymd(now.year(), now.month(), 1).and_hms(0, 0, 0);
         let (next_year, next_month) = if now.month() == 12 {
             (now.year() + 1, 1)
@@ -42,25 +51,62 @@ fn main() -> io::Result<()> {
 
         let mut input = String::new();
         io::stdin().read_to_string(&mut input).unwrap();
-        let all_payments = AllPayments::from_json(&input).unwrap();
+        let mut all_payments = AllPayments::from_json(&input).unwrap();
+        if let Some(rates_file) = args().nth(1) {
+            all_payments.set_exchange_rates(ExchangeRates::from_file(rates_file).unwrap());
+        }
 
-        let range = all_payments.payments().range(
-            &PaymentId::new(start_of_month.into())..&PaymentId::new(start_of_next_month.into()),
+        let month = all_payments
+            .query()
+            .between(start_of_month.into(), start_of_next_month.into());
+        data = (month.group_by(&[GroupBy::Day]).unwrap())
+            .time_series(
+                start_of_month.into(),
+                start_of_next_month.into(),
+                Period::Day,
+            )
+            .unwrap();
+
+        // the rest of the month is projected from the spending so far
+        let forecast = all_payments
+            .forecast(
+                start_of_month.into(),
+                start_of_next_month.into(),
+                today,
+                ForecastMethod::RunRate,
+            )
+            .unwrap();
+        data.truncate(now.day() as usize);
+        band = data.iter().map(|&spent| (spent, spent)).collect();
+        data.extend(forecast.daily());
+        band.extend(
+            forecast
+                .daily_low()
+                .iter()
+                .copied()
+                .zip(forecast.daily_high().iter().copied()),
         );
-        data = vec![0; days_in_month as usize];
-        ignore = (now.day()..days_in_month as u32).collect::<Vec<u32>>();
-        for (id, _det) in range {
-            let orders = all_payments.payments().get(id).unwrap().orders();
-            let mut sum = 0;
-            for orderdet in orders.values() {
-                sum += orderdet.unit_price() * orderdet.quantity();
+        projected = (now.day()..days_in_month as u32).collect::<Vec<u32>>();
+        currency = all_payments.base_currency().symbol().to_string();
+
+        // daily cutout from the tightest budget over all spending that runs
+        // today, budgets whose status cannot be worked out are left out
+        let mut limits = vec![];
+        for (name, budget) in all_payments.budgets() {
+            if budget.scope() != &BudgetScope::All {
+                continue;
+            }
+            if let Ok(status) = all_payments.budget_status(name, today)
+                && status.start() <= &today
+                && &today < status.end()
+            {
+                limits.push(status.daily_limit());
             }
-            data[id.date().get_fields().unwrap().day0() as usize] += sum;
         }
+        cutout =
+            (limits.into_iter().reduce(f64::min)).unwrap_or_else(|| 30_000.0 / data.len() as f64);
     }
 
-    let cutout = 30_000.0 / data.len() as f64;
-
     enable_raw_mode()?;
 
     // Enable mouse capture
@@ -73,26 +119,24 @@ fn main() -> io::Result<()> {
         crossterm::cursor::MoveTo(0, 0),
     )?;
 
-    render(&data, &ignore, cutout);
+    render(&data, &ignore, (&projected, &band), cutout, &currency);
 
     let (mut x, mut y) = (0, 0);
     loop {
         if event::poll(Duration::from_millis(100)).unwrap() {
             match event::read()? {
-                Event::Key(key_event) => {
-                    if key_event.code == KeyCode::Char('q') {
-                        break;
-                    }
+                Event::Key(key_event) if key_event.code == KeyCode::Char('q') => {
+                    break;
                 }
                 Event::Resize(new_x, new_y) => {
-                    render(&data, &ignore, cutout);
+                    render(&data, &ignore, (&projected, &band), cutout, &currency);
                     (x, y) = (new_x, new_y);
                 }
                 _ => {}
             }
         }
         if crossterm::terminal::size().unwrap() != (x, y) {
-            render(&data, &ignore, cutout);
+            render(&data, &ignore, (&projected, &band), cutout, &currency);
             (x, y) = crossterm::terminal::size().unwrap();
         }
     }
@@ -108,7 +152,13 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
-fn render(data: &[u32], ignore: &[u32], cutout: f64) {
+fn render(
+    data: &[i64],
+    ignore: &[u32],
+    (projected, band): (&[u32], &[(i64, i64)]),
+    cutout: f64,
+    currency: &str,
+) {
     let white = &" ".on(Color::White).to_string();
     let white2 = &"  ".on(Color::White).to_string();
     let symbols = [
@@ -120,7 +170,15 @@ fn render(data: &[u32], ignore: &[u32], cutout: f64) {
     let box_sym = symbols[3];
     let width = crossterm::terminal::size().unwrap().0 - 4;
     let height = crossterm::terminal::size().unwrap().1 - 2;
-    let graph = bar_graph_horizontal_label(data, width as u32, height as u32, cutout, ignore);
+    let graph = bar_graph_horizontal_label_band(
+        data,
+        width as u32,
+        height as u32,
+        cutout,
+        ignore,
+        (projected, band),
+        currency,
+    );
     let mut area = graph.area().clone();
 
     execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0)).unwrap();
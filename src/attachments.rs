@@ -0,0 +1,214 @@
+use crate::{
+    crypto::{check_key, decrypt, encrypt, from_hex, to_hex},
+    error::{Error, Result},
+    fs::{create_dir, list_dir, read_file, write_file},
+    payments::{AllPayments, PaymentId},
+};
+use derive_getters::Getters;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// hmac-sha256 of the plain attachment contents under the dataset key, also
+/// used as its file name; being keyed, it does not tell which known file is
+/// stored to anyone without the key
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct AttachmentHash {
+    bytes: [u8; 32],
+}
+
+/// encrypted attachment files stored in a directory next to the data file
+#[derive(Getters, Debug, Clone)]
+pub struct AttachmentStore {
+    dir: PathBuf,
+    key: Vec<u8>,
+}
+
+/// attachments referenced by payments but not stored, and stored files no
+/// payment references
+#[derive(Getters, Debug, PartialEq, Eq, Default)]
+pub struct AttachmentCheck {
+    missing: BTreeMap<AttachmentHash, Vec<PaymentId>>,
+    unreferenced: BTreeSet<AttachmentHash>,
+}
+
+impl AttachmentHash {
+    pub fn of(key: &[u8], contents: &[u8]) -> Result<Self> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(key).map_err(|_| Error::InvalidKeyLength(key.len()))?;
+        mac.update(contents);
+        Ok(Self {
+            bytes: mac.finalize().into_bytes().into(),
+        })
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        from_hex(hex)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(|bytes| Self { bytes })
+            .ok_or_else(|| Error::InvalidAttachment(hex.to_string()))
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.bytes)
+    }
+}
+
+impl Display for AttachmentHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+impl Serialize for AttachmentHash {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.to_hex().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttachmentHash {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let hex: String = Deserialize::deserialize(deserializer)?;
+        AttachmentHash::from_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+impl AttachmentStore {
+    pub fn new<P: AsRef<Path>>(dir: P, key: &[u8]) -> Result<Self> {
+        check_key(key)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            key: key.to_vec(),
+        })
+    }
+
+    /// store in the `attachments` directory beside the data file
+    pub fn next_to<P: AsRef<Path>>(data_file: P, key: &[u8]) -> Result<Self> {
+        let dir = data_file.as_ref().parent().unwrap_or(Path::new("."));
+        Self::new(dir.join(ATTACHMENTS_DIR), key)
+    }
+
+    fn path(&self, hash: &AttachmentHash) -> PathBuf {
+        self.dir.join(hash.to_hex())
+    }
+
+    /// encrypts and writes the contents, storing the same file twice only
+    /// writes it once
+    pub fn store(&self, contents: &[u8]) -> Result<AttachmentHash> {
+        let hash = AttachmentHash::of(&self.key, contents)?;
+        let path = self.path(&hash);
+        if !path.exists() {
+            create_dir(&self.dir)?;
+            write_file(path, encrypt(&self.key, contents)?)?;
+        }
+        Ok(hash)
+    }
+
+    pub fn load(&self, hash: &AttachmentHash) -> Result<Vec<u8>> {
+        let contents = decrypt(&self.key, &read_file(self.path(hash))?)?;
+        if AttachmentHash::of(&self.key, &contents)? != *hash {
+            return Err(Error::InvalidAttachment(hash.to_hex()));
+        }
+        Ok(contents)
+    }
+
+    /// hashes of the stored files, other files in the directory are ignored
+    pub fn list(&self) -> Result<BTreeSet<AttachmentHash>> {
+        if !self.dir.exists() {
+            return Ok(BTreeSet::new());
+        }
+        Ok(list_dir(&self.dir)?
+            .iter()
+            .filter_map(|name| AttachmentHash::from_hex(name).ok())
+            .collect())
+    }
+}
+
+impl AllPayments {
+    pub fn check_attachments(&self, store: &AttachmentStore) -> Result<AttachmentCheck> {
+        let mut unreferenced = store.list()?;
+        let mut missing = BTreeMap::<AttachmentHash, Vec<PaymentId>>::new();
+        for (payid, payment) in self.payments() {
+            for hash in payment.attachments() {
+                if !unreferenced.remove(hash) && !store.path(hash).exists() {
+                    missing.entry(*hash).or_default().push(payid.clone());
+                }
+            }
+        }
+        Ok(AttachmentCheck {
+            missing,
+            unreferenced,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttachmentHash, AttachmentStore};
+    use crate::{
+        payments::{AllPayments, PaymentDetail, PaymentId, ValueSet},
+        time::FakeUtcTime,
+        types::internment::CustomString,
+    };
+    use std::{env::temp_dir, fs::remove_dir_all};
+
+    #[test]
+    fn attachments() {
+        let dir = temp_dir().join(format!("track-payments-attachments-{}", std::process::id()));
+        let key = "12345678901234567890123456789012".as_bytes();
+        assert!(AttachmentStore::new(&dir, &key[..31]).is_err());
+        let store = AttachmentStore::new(&dir, key).unwrap();
+
+        let receipt = "scanned receipt".as_bytes();
+        let hash = store.store(receipt).unwrap();
+        assert_eq!(hash, store.store(receipt).unwrap());
+        assert_eq!(AttachmentHash::from_hex(&hash.to_hex()).unwrap(), hash);
+        assert_eq!(store.load(&hash).unwrap(), receipt);
+        assert_ne!(AttachmentHash::of(&[0; 32], receipt).unwrap(), hash);
+        let orphan = store.store("old receipt".as_bytes()).unwrap();
+
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Cash")],
+            vec![],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/11 08:00").unwrap());
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        all_payments
+            .set_payment_note(&payid, Some("birthday cake".into()))
+            .unwrap();
+        all_payments.attach(&payid, hash).unwrap();
+        let lost = AttachmentHash::of(key, "lost receipt".as_bytes()).unwrap();
+        all_payments.attach(&payid, lost).unwrap();
+
+        let check = all_payments.check_attachments(&store).unwrap();
+        assert_eq!(check.missing().keys().collect::<Vec<_>>(), vec![&lost]);
+        assert_eq!(
+            check.unreferenced().iter().collect::<Vec<_>>(),
+            vec![&orphan]
+        );
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,11 +1,23 @@
 use crate::error::{Error, Result};
 use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
-use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::{Aes256Gcm, Nonce};
+
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+pub fn check_key(key_str: &[u8]) -> Result<()> {
+    (key_str.len() == KEY_LEN)
+        .then_some(())
+        .ok_or(Error::InvalidKeyLength(key_str.len()))
+}
+
+fn cipher(key_str: &[u8]) -> Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(key_str).map_err(|_| Error::InvalidKeyLength(key_str.len()))
+}
 
 pub fn encrypt(key_str: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
-    let key = Key::<Aes256Gcm>::from_slice(key_str);
+    let cipher = cipher(key_str)?;
     let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher = Aes256Gcm::new(key);
     let ciphered_data = cipher
         .encrypt(&nonce, plaintext)
         .map_err(|_| Error::EncryptionFailed)?;
@@ -18,11 +30,15 @@ pub fn encrypt_str(key_str: &[u8], plaintext: &str) -> Result<Vec<u8>> {
     encrypt(key_str, plaintext.as_bytes())
 }
 
+/// the nonce is read from the first bytes, data too short to hold one
+/// fails like any other damaged data
 pub fn decrypt(key_str: &[u8], encrypted_data: &[u8]) -> Result<Vec<u8>> {
-    let key = Key::<Aes256Gcm>::from_slice(key_str);
-    let (nonce_arr, ciphered_data) = encrypted_data.split_at(12);
+    let cipher = cipher(key_str)?;
+    let (nonce_arr, ciphered_data) = encrypted_data
+        .split_at_checked(NONCE_LEN)
+        .ok_or(Error::DecryptionFailed)?;
     let nonce = Nonce::from_slice(nonce_arr);
-    Aes256Gcm::new(key)
+    cipher
         .decrypt(nonce, ciphered_data)
         .map_err(|_| Error::DecryptionFailed)
 }
@@ -31,9 +47,37 @@ pub fn decrypt_str(key_str: &[u8], encrypted_data: &[u8]) -> Result<String> {
     String::from_utf8(decrypt(key_str, encrypted_data)?).map_err(Error::from_generic)
 }
 
+/// lowercase hex digits, two per byte
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// bytes of a string of hex digits, none if it has an odd length or any
+/// other character
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// encrypted text as a single line of hex digits, e.g. for line based files
+pub fn encrypt_str_hex(key_str: &[u8], plaintext: &str) -> Result<String> {
+    encrypt_str(key_str, plaintext).map(|encrypted_data| to_hex(&encrypted_data))
+}
+
+pub fn decrypt_str_hex(key_str: &[u8], encrypted_hex: &str) -> Result<String> {
+    let encrypted_data = from_hex(encrypted_hex).ok_or(Error::DecryptionFailed)?;
+    decrypt_str(key_str, &encrypted_data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{decrypt, decrypt_str, encrypt, encrypt_str};
+    use crate::error::Error;
 
     #[test]
     fn encryption() {
@@ -42,6 +86,14 @@ mod tests {
         let cipher_text = encrypt(key_str, plain_text).unwrap();
         let decipher_text = decrypt(key_str, &cipher_text).unwrap();
         assert_eq!(plain_text, decipher_text);
+        assert!(matches!(
+            decrypt(key_str, &cipher_text[..5]),
+            Err(Error::DecryptionFailed)
+        ));
+        assert!(matches!(
+            encrypt(&key_str[..16], plain_text),
+            Err(Error::InvalidKeyLength(16))
+        ));
     }
 
     #[test]
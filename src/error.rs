@@ -1,6 +1,10 @@
 use crate::{
-    payments::{OrderId, PaymentId, ValueSet},
+    payments::{
+        BudgetPeriod, GroupBy, IncomeId, OrderId, PaymentId, Recurrence, TransferId, ValueKind,
+        ValueReferences, ValueSet,
+    },
     time::FakeUtcTime,
+    types::{currency::Currency, discount::Discount, internment::CustomString, money::Money},
 };
 use chrono::ParseError;
 use serde_json::Error as JsonError;
@@ -11,16 +15,50 @@ use std::io::Error as IoError;
 pub enum Error {
     PaymentDuplicated(PaymentId),
     PaymentNotFound(PaymentId),
+    IdsExhausted(FakeUtcTime),
     OrderDuplicated(PaymentId, OrderId),
     OrderNotFound(PaymentId, OrderId),
-    MissingElements(ValueSet),
+    OrderCollision(PaymentId, OrderId),
+    IncomeDuplicated(IncomeId),
+    IncomeNotFound(IncomeId),
+    AccountDuplicated(CustomString),
+    AccountNotFound(CustomString),
+    AccountInUse(CustomString),
+    SameAccountTransfer(CustomString),
+    TransferDuplicated(TransferId),
+    TransferNotFound(TransferId),
+    MissingElements(Box<ValueSet>),
+    ValueDuplicated(ValueKind, CustomString),
+    ValueInUse(ValueKind, CustomString, Box<ValueReferences>),
+    AllocationMismatch(PaymentId, Money, Money),
+    EmptyShare,
+    CategoryCycle(CustomString),
+    RecurringDuplicated(CustomString),
+    RecurringNotFound(CustomString),
+    InvalidRecurrence(Recurrence),
+    InvalidGrouping(Vec<GroupBy>),
+    BudgetDuplicated(CustomString),
+    BudgetNotFound(CustomString),
+    InvalidBudgetPeriod(BudgetPeriod),
     TimeParseFailed(ParseError),
     TimeFormatFailed(FakeUtcTime),
+    MoneyOverflow,
+    InvalidCents(i64, i8),
+    InvalidQuantity(String),
+    DivisionByZero,
+    InvalidDiscount(Discount),
+    InvalidCurrency(String),
+    ExchangeRateMissing(Currency, Currency, FakeUtcTime),
+    InvalidExchangeRate(Currency, Currency, FakeUtcTime),
+    InvalidAttachment(String),
+    InvalidKeyLength(usize),
     EncryptionFailed,
     DecryptionFailed,
     JsonParseFailed(JsonError),
     JsonDumpFailed(JsonError),
     FileError(IoError),
+    BatchFailed(Vec<(usize, Error)>),
+    JsonEntriesFailed(Vec<(&'static str, usize, Error)>),
     Generic(String),
 }
 
@@ -37,16 +75,78 @@ impl Display for Error {
         let fmt = match self {
             Error::PaymentDuplicated(pay) => format!("payment duplicated: {:?}", pay),
             Error::PaymentNotFound(pay) => format!("payment not found: {:?}", pay),
+            Error::IdsExhausted(date) => format!("no free id left at: {date:?}"),
             Error::OrderDuplicated(pay, ord) => format!("order duplicated: {:?}, {ord:?}", pay),
             Error::OrderNotFound(pay, ord) => format!("order not found: {:?}, {ord:?}", pay),
+            Error::OrderCollision(pay, ord) => {
+                format!("order collision: {pay:?}, {ord:?}")
+            }
+            Error::IncomeDuplicated(income) => format!("income duplicated: {income:?}"),
+            Error::IncomeNotFound(income) => format!("income not found: {income:?}"),
+            Error::AccountDuplicated(name) => format!("account duplicated: {}", name.as_str()),
+            Error::AccountNotFound(name) => format!("account not found: {}", name.as_str()),
+            Error::AccountInUse(name) => {
+                format!("account still used by transfers: {}", name.as_str())
+            }
+            Error::SameAccountTransfer(name) => {
+                format!("transfer to the same account: {}", name.as_str())
+            }
+            Error::TransferDuplicated(transfer) => format!("transfer duplicated: {transfer:?}"),
+            Error::TransferNotFound(transfer) => format!("transfer not found: {transfer:?}"),
             Error::MissingElements(value_set) => format!("missing values: {value_set:?}"),
+            Error::ValueDuplicated(kind, value) => {
+                format!("{kind} duplicated: {}", value.as_str())
+            }
+            Error::ValueInUse(kind, value, references) => {
+                format!("{kind} in use: {}, {references:?}", value.as_str())
+            }
+            Error::AllocationMismatch(pay, total, allocated) => {
+                format!("allocations mismatch: {pay:?}, total {total}, allocated {allocated}")
+            }
+            Error::EmptyShare => String::from("share without participants"),
+            Error::CategoryCycle(category) => format!("category cycle: {}", category.as_str()),
+            Error::RecurringDuplicated(name) => format!("recurring duplicated: {}", name.as_str()),
+            Error::RecurringNotFound(name) => format!("recurring not found: {}", name.as_str()),
+            Error::InvalidRecurrence(rule) => format!("invalid recurrence: {rule:?}"),
+            Error::InvalidGrouping(keys) => format!("invalid grouping: {keys:?}"),
+            Error::BudgetDuplicated(name) => format!("budget duplicated: {}", name.as_str()),
+            Error::BudgetNotFound(name) => format!("budget not found: {}", name.as_str()),
+            Error::InvalidBudgetPeriod(period) => format!("invalid budget period: {period:?}"),
             Error::TimeParseFailed(parse_error) => format!("parsing time failed: {parse_error}"),
             Error::TimeFormatFailed(time) => format!("formatting time failed: {:?}", time),
+            Error::MoneyOverflow => String::from("money overflow"),
+            Error::InvalidCents(units, cents) => format!("invalid cents: {units}, {cents}"),
+            Error::InvalidQuantity(amount) => format!("invalid quantity: {amount}"),
+            Error::DivisionByZero => String::from("division by zero"),
+            Error::InvalidDiscount(discount) => format!("invalid discount: {discount:?}"),
+            Error::InvalidCurrency(code) => format!("invalid currency: {code}"),
+            Error::ExchangeRateMissing(from, to, time) => {
+                format!("missing exchange rate: {from} -> {to} at {time:?}")
+            }
+            Error::InvalidExchangeRate(from, to, time) => {
+                format!("invalid exchange rate: {from} -> {to} at {time:?}")
+            }
+            Error::InvalidAttachment(hash) => format!("invalid attachment: {hash}"),
+            Error::InvalidKeyLength(len) => format!("invalid key length: {len}"),
             Error::EncryptionFailed => String::from("encryption failed"),
             Error::DecryptionFailed => String::from("decryption failed"),
             Error::JsonParseFailed(err) => format!("json parsing failed: {err}"),
             Error::JsonDumpFailed(err) => format!("json dumping failed: {err}"),
             Error::FileError(err) => format!("file error: {err}"),
+            Error::BatchFailed(errors) => {
+                let errors: Vec<String> = (errors.iter())
+                    .map(|(index, err)| format!("#{index}: {}", err.to_string().trim_end()))
+                    .collect();
+                format!("batch failed: {}", errors.join("; "))
+            }
+            Error::JsonEntriesFailed(errors) => {
+                let errors: Vec<String> = (errors.iter())
+                    .map(|(section, index, err)| {
+                        format!("{section}[{index}]: {}", err.to_string().trim_end())
+                    })
+                    .collect();
+                format!("json entries failed: {}", errors.join("; "))
+            }
             Error::Generic(err) => format!("generic error: {err}"),
         };
         writeln!(f, "{fmt}")
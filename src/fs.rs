@@ -1,8 +1,8 @@
 use crate::error::{Error, Result};
 use std::{
     env::current_exe,
-    fs::read,
-    fs::write,
+    fs::{File, OpenOptions, create_dir_all, read, read_dir, write},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 
@@ -28,6 +28,51 @@ pub fn write_file<P: AsRef<Path>>(path: P, contents: Vec<u8>) -> Result<()> {
     write(path, contents).map_err(Error::FileError)
 }
 
+/// appends to the end of the file, creating it if needed
+pub fn append_file<P: AsRef<Path>>(path: P, contents: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(Error::FileError)?;
+    file.write_all(contents).map_err(Error::FileError)
+}
+
+/// the last byte of the file, none if it is empty
+pub fn last_byte<P: AsRef<Path>>(path: P) -> Result<Option<u8>> {
+    let mut file = File::open(path).map_err(Error::FileError)?;
+    if file.seek(SeekFrom::End(0)).map_err(Error::FileError)? == 0 {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::End(-1)).map_err(Error::FileError)?;
+    let mut byte = [0];
+    file.read_exact(&mut byte).map_err(Error::FileError)?;
+    Ok(Some(byte[0]))
+}
+
+/// cuts the file down to its first `len` bytes
+pub fn truncate_file<P: AsRef<Path>>(path: P, len: u64) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(Error::FileError)?;
+    file.set_len(len).map_err(Error::FileError)
+}
+
+pub fn create_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    create_dir_all(path).map_err(Error::FileError)
+}
+
+/// names of the entries in the directory
+pub fn list_dir<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in read_dir(path).map_err(Error::FileError)? {
+        let entry = entry.map_err(Error::FileError)?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{get_exe_dir, get_exe_path};
@@ -1,7 +1,9 @@
+pub mod attachments;
 pub mod crypto;
 pub mod error;
 pub mod fs;
 pub mod payments;
+pub mod rates;
 pub mod renderer;
 pub mod time;
 pub mod types;
@@ -0,0 +1,286 @@
+use super::{AllPayments, PaymentId, ValueSet, next_id};
+use crate::{
+    error::{Error, Result},
+    time::FakeUtcTime,
+    types::{currency::Currency, internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+
+/// transfers are identified by date and sequence number, like payments
+pub type TransferId = PaymentId;
+
+/// a payment method tracked as an account, with a balance starting from
+/// `opening_balance` on the `opened` date
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct Account {
+    opened: FakeUtcTime,
+    opening_balance: Money,
+    currency: Option<Currency>,
+}
+
+/// money moved between two accounts, e.g. an ATM withdrawal from the bank
+/// into the cash wallet, `amount` is in the currency of `from`
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct AccountTransfer {
+    from: CustomString,
+    to: CustomString,
+    amount: Money,
+}
+
+impl Account {
+    pub fn new(opened: FakeUtcTime, opening_balance: Money) -> Self {
+        Self {
+            opened,
+            opening_balance,
+            currency: None,
+        }
+    }
+
+    /// balance is kept in `currency` instead of the dataset base currency
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+}
+
+impl AccountTransfer {
+    pub fn new(from: CustomString, to: CustomString, amount: Money) -> Self {
+        Self { from, to, amount }
+    }
+
+    pub(super) fn replace_method(&mut self, from: &CustomString, to: &CustomString) {
+        for method in [&mut self.from, &mut self.to] {
+            if method == from {
+                *method = to.clone();
+            }
+        }
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        let mut values = ValueSet::new();
+        for method in [&self.from, &self.to] {
+            if !valid_values.methods.contains(method) {
+                values.add_values(vec![], vec![], vec![method.clone()], vec![]);
+            }
+        }
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+}
+
+impl AllPayments {
+    pub fn account_currency(&self, account: &Account) -> Currency {
+        account.currency.unwrap_or(self.base_currency)
+    }
+
+    pub fn add_account(&mut self, method: CustomString, account: Account) -> Result<()> {
+        if self.accounts.contains_key(&method) {
+            return Err(Error::AccountDuplicated(method));
+        }
+        if !self.value_set.methods.contains(&method) {
+            let mut values = ValueSet::new();
+            values.add_values(vec![], vec![], vec![method], vec![]);
+            return Err(Error::MissingElements(Box::new(values)));
+        }
+
+        assert!(self.accounts.insert(method, account).is_none());
+
+        Ok(())
+    }
+
+    pub fn remove_account(&mut self, method: &CustomString) -> Result<()> {
+        if self
+            .transfers
+            .values()
+            .any(|transfer| &transfer.from == method || &transfer.to == method)
+        {
+            return Err(Error::AccountInUse(method.clone()));
+        }
+        self.accounts
+            .remove(method)
+            .map(|_| ())
+            .ok_or_else(|| Error::AccountNotFound(method.clone()))
+    }
+
+    pub fn next_transfer_id(&self, date: FakeUtcTime) -> Result<TransferId> {
+        next_id(&self.transfers, date)
+    }
+
+    pub fn add_transfer(
+        &mut self,
+        transferid: TransferId,
+        transfer: AccountTransfer,
+    ) -> Result<()> {
+        if self.transfers.contains_key(&transferid) {
+            return Err(Error::TransferDuplicated(transferid));
+        }
+        transfer.check_missing_elements(&self.value_set)?;
+        for method in [&transfer.from, &transfer.to] {
+            if !self.accounts.contains_key(method) {
+                return Err(Error::AccountNotFound(method.clone()));
+            }
+        }
+        if transfer.from == transfer.to {
+            return Err(Error::SameAccountTransfer(transfer.from.clone()));
+        }
+
+        assert!(self.transfers.insert(transferid, transfer).is_none());
+
+        Ok(())
+    }
+
+    pub fn remove_transfer(&mut self, transferid: &TransferId) -> Result<()> {
+        self.transfers
+            .remove(transferid)
+            .map(|_| ())
+            .ok_or_else(|| Error::TransferNotFound(transferid.clone()))
+    }
+
+    /// balance of the account right before `at`, in the account currency:
+    /// the opening balance plus incomes and incoming transfers, minus
+    /// payments and outgoing transfers dated from the opening up to `at`
+    pub fn account_balance(&self, method: &CustomString, at: FakeUtcTime) -> Result<Money> {
+        let account = self
+            .accounts
+            .get(method)
+            .ok_or_else(|| Error::AccountNotFound(method.clone()))?;
+        if at <= account.opened {
+            return Ok(Money::default());
+        }
+        let currency = self.account_currency(account);
+        let range = PaymentId::new(account.opened)..PaymentId::new(at);
+
+        let mut balance = account.opening_balance;
+        for (payid, payment) in self.payments.range(range.clone()) {
+            let Some(&amount) = payment.calculate_method_amounts(payid)?.get(method) else {
+                continue;
+            };
+            let from = self.payment_currency(&payment.payment_details);
+            let amount = (self.exchange_rates).convert(amount, from, currency, payid.date)?;
+            balance = balance.checked_sub(amount)?;
+        }
+        for (incomeid, income) in self.incomes.range(range.clone()) {
+            if income.account() != method {
+                continue;
+            }
+            let from = income.currency().unwrap_or(self.base_currency);
+            let amount = (self.exchange_rates).convert(
+                *income.amount(),
+                from,
+                currency,
+                *incomeid.date(),
+            )?;
+            balance = balance.checked_add(amount)?;
+        }
+        for (transferid, transfer) in self.transfers.range(range) {
+            if &transfer.from == method {
+                balance = balance.checked_sub(transfer.amount)?;
+            } else if &transfer.to == method {
+                let from = self.account_currency(&self.accounts[&transfer.from]);
+                let amount = (self.exchange_rates).convert(
+                    transfer.amount,
+                    from,
+                    currency,
+                    transferid.date,
+                )?;
+                balance = balance.checked_add(amount)?;
+            }
+        }
+        Ok(balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Account, AccountTransfer, TransferId};
+    use crate::{
+        error::Error,
+        payments::{
+            AllPayments, IncomeDetail, IncomeId, OrderDetail, OrderId, PaymentDetail, PaymentId,
+            ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn account_balance() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Bank"), CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        values.add_sources(vec![CustomString::from("Salary")]);
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        let opened = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let bank = CustomString::from("Bank");
+        let cash = CustomString::from("Cash");
+        all_payments
+            .add_account(bank.clone(), Account::new(opened, Money::new(100_000)))
+            .unwrap();
+        let withdrawal = AccountTransfer::new(bank.clone(), cash.clone(), Money::new(5_000));
+        let date = FakeUtcTime::parse_str("2024/01/10 10:00").unwrap();
+        assert!((all_payments.add_transfer(TransferId::new(date), withdrawal.clone())).is_err());
+        all_payments
+            .add_account(cash.clone(), Account::new(opened, Money::new(0)))
+            .unwrap();
+        all_payments
+            .add_transfer(TransferId::new(date), withdrawal)
+            .unwrap();
+        let to_itself = AccountTransfer::new(cash.clone(), cash.clone(), Money::new(100));
+        assert!(matches!(
+            all_payments.add_transfer(TransferId::new(opened), to_itself),
+            Err(Error::SameAccountTransfer(_))
+        ));
+
+        let date = FakeUtcTime::parse_str("2024/01/11 08:00").unwrap();
+        let payid = PaymentId::new(date);
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), cash.clone());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        let orderdetail = OrderDetail::new(Money::new(350), 2);
+        all_payments
+            .add_order(&payid, OrderId::new("Bread".into()), orderdetail)
+            .unwrap();
+
+        let date = FakeUtcTime::parse_str("2024/01/27 09:00").unwrap();
+        let salary = IncomeDetail::new("Salary".into(), Money::new(200_000), bank.clone());
+        all_payments
+            .add_income(IncomeId::new(date), salary)
+            .unwrap();
+
+        let at = FakeUtcTime::parse_str("2024/01/11 00:00").unwrap();
+        assert_eq!(
+            all_payments.account_balance(&bank, at).unwrap().cents(),
+            95_000
+        );
+        assert_eq!(
+            all_payments.account_balance(&cash, at).unwrap().cents(),
+            5_000
+        );
+        let at = FakeUtcTime::parse_str("2024/02/01 00:00").unwrap();
+        assert_eq!(
+            all_payments.account_balance(&bank, at).unwrap().cents(),
+            295_000
+        );
+        assert_eq!(
+            all_payments.account_balance(&cash, at).unwrap().cents(),
+            4_300
+        );
+        assert!(matches!(
+            all_payments.remove_account(&cash),
+            Err(Error::AccountInUse(_))
+        ));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
@@ -0,0 +1,269 @@
+use super::{PayOrdersDetail, PaymentDetail, PaymentId, Query};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcTime, Period},
+    types::{internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum GroupBy {
+    Day,
+    /// ISO weeks, starting on Monday
+    Week,
+    Month,
+    Year,
+    City,
+    Shop,
+    /// split payments are divided between their methods
+    Method,
+    Item,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum GroupKey {
+    /// start of the day, week, month or year
+    Time(FakeUtcTime),
+    Value(CustomString),
+}
+
+/// amounts are order lines when grouping by item, payments otherwise
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Aggregate {
+    sum: Money,
+    count: u64,
+    min: Money,
+    max: Money,
+}
+
+/// groups ordered by their keys, which follow the order of the `GroupBy`
+/// list
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Aggregation {
+    keys: Vec<GroupBy>,
+    groups: BTreeMap<Vec<GroupKey>, Aggregate>,
+}
+
+impl From<Period> for GroupBy {
+    fn from(period: Period) -> Self {
+        match period {
+            Period::Day => GroupBy::Day,
+            Period::Week => GroupBy::Week,
+            Period::Month => GroupBy::Month,
+            Period::Year => GroupBy::Year,
+        }
+    }
+}
+
+impl Aggregate {
+    fn add(&mut self, amount: Money) -> Result<()> {
+        if self.count == 0 {
+            (self.min, self.max) = (amount, amount);
+        }
+        self.sum = self.sum.checked_add(amount)?;
+        self.count += 1;
+        self.min = self.min.min(amount);
+        self.max = self.max.max(amount);
+        Ok(())
+    }
+
+    /// rounded half away from zero, zero for an empty group
+    pub fn average(&self) -> Result<Money> {
+        if self.count == 0 {
+            return Ok(Money::default());
+        }
+        let count = i64::try_from(self.count).map_err(|_| Error::MoneyOverflow)?;
+        self.sum.checked_scale(1, count)
+    }
+}
+
+impl Aggregation {
+    /// sum of every group in key order, in cents
+    pub fn sums(&self) -> Vec<i64> {
+        self.groups
+            .values()
+            .map(|group| group.sum.cents())
+            .collect()
+    }
+
+    /// sums of a grouping by `period` alone, with a zero for each period
+    /// from `start` to `end` that has no spending, ready for a bar chart
+    pub fn time_series(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+        period: Period,
+    ) -> Result<Vec<i64>> {
+        if self.keys != [GroupBy::from(period)] {
+            return Err(Error::InvalidGrouping(self.keys.clone()));
+        }
+        let mut series = vec![];
+        let mut period_start = start.period_start(period)?;
+        while period_start < end {
+            let key = vec![GroupKey::Time(period_start)];
+            series.push(self.groups.get(&key).map_or(0, |group| group.sum.cents()));
+            period_start = period_start.period_end(period)?;
+        }
+        Ok(series)
+    }
+}
+
+impl GroupBy {
+    /// key of a payment share paid with `method`, none when grouping by item
+    fn payment_key(
+        &self,
+        payid: &PaymentId,
+        details: &PaymentDetail,
+        method: &CustomString,
+    ) -> Result<Option<GroupKey>> {
+        let period = match self {
+            GroupBy::Day => Period::Day,
+            GroupBy::Week => Period::Week,
+            GroupBy::Month => Period::Month,
+            GroupBy::Year => Period::Year,
+            GroupBy::City => return Ok(Some(GroupKey::Value(details.city().clone()))),
+            GroupBy::Shop => return Ok(Some(GroupKey::Value(details.shop().clone()))),
+            GroupBy::Method => return Ok(Some(GroupKey::Value(method.clone()))),
+            GroupBy::Item => return Ok(None),
+        };
+        Ok(Some(GroupKey::Time(payid.date().period_start(period)?)))
+    }
+}
+
+impl Aggregation {
+    /// adds `amount` of the payment, or of its `item` line, to its groups
+    fn add(
+        &mut self,
+        payid: &PaymentId,
+        payment: &PayOrdersDetail,
+        item: Option<&CustomString>,
+        amount: Money,
+    ) -> Result<()> {
+        let details = payment.payment_details();
+        let shares = match self.keys.contains(&GroupBy::Method) {
+            true => payment.split_by_method(payid, amount)?,
+            false => vec![(details.method().clone(), amount)],
+        };
+        for (method, amount) in shares {
+            let mut group = Vec::with_capacity(self.keys.len());
+            for key in &self.keys {
+                let key = key.payment_key(payid, details, &method)?;
+                group.extend(key.or_else(|| item.cloned().map(GroupKey::Value)));
+            }
+            self.groups.entry(group).or_default().add(amount)?;
+        }
+        Ok(())
+    }
+}
+
+impl Query<'_> {
+    /// aggregates the matching payments, or order lines when grouping by
+    /// item, by every combination of `keys`
+    pub fn group_by(&self, keys: &[GroupBy]) -> Result<Aggregation> {
+        let mut aggregation = Aggregation {
+            keys: keys.to_vec(),
+            groups: BTreeMap::new(),
+        };
+        if keys.contains(&GroupBy::Item) {
+            for line in self.lines() {
+                let line = line?;
+                let item = Some(line.orderid().item());
+                aggregation.add(line.payid(), line.payment(), item, *line.amount())?;
+            }
+        } else {
+            for payment in self.amounts() {
+                let (payid, payment, amount) = payment?;
+                aggregation.add(payid, payment, None, amount)?;
+            }
+        }
+        Ok(aggregation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupBy, GroupKey};
+    use crate::{
+        error::Error,
+        payments::{
+            AllPayments, MethodAllocation, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet,
+        },
+        time::{FakeUtcTime, Period},
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn group_by() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome"), CustomString::from("Milan")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash"), CustomString::from("Card")],
+            vec![CustomString::from("Bread"), CustomString::from("Wine")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let payments = [
+            (
+                "2024/01/10 10:00",
+                "Rome",
+                vec![("Bread", 300), ("Wine", 1200)],
+            ),
+            ("2024/01/10 18:00", "Rome", vec![("Bread", 200)]),
+            ("2024/01/20 10:00", "Milan", vec![("Bread", 250)]),
+            ("2024/03/10 10:00", "Rome", vec![("Wine", 900)]),
+        ];
+        for (date, city, orders) in payments {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new(city.into(), "Market".into(), "Cash".into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            for (item, price) in orders {
+                let orderdetail = OrderDetail::new(Money::new(price), 1);
+                all_payments
+                    .add_order(&payid, OrderId::new(item.into()), orderdetail)
+                    .unwrap();
+            }
+        }
+
+        let query = all_payments.query();
+        let by_city = query.group_by(&[GroupBy::Month, GroupBy::City]).unwrap();
+        assert_eq!(by_city.sums(), vec![250, 1700, 900]);
+        let january = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let rome = vec![GroupKey::Time(january), GroupKey::Value("Rome".into())];
+        let rome = by_city.groups()[&rome];
+        assert_eq!(
+            (*rome.count(), rome.min().cents(), rome.max().cents()),
+            (2, 200, 1500)
+        );
+        assert_eq!(rome.average().unwrap().cents(), 850);
+
+        let by_item = query.group_by(&[GroupBy::Item]).unwrap();
+        let bread = by_item.groups()[&vec![GroupKey::Value("Bread".into())]];
+        assert_eq!((*bread.count(), bread.sum().cents()), (3, 750));
+
+        let by_month = query.group_by(&[GroupBy::Month]).unwrap();
+        let end = FakeUtcTime::parse_str("2024/05/01 00:00").unwrap();
+        let series = by_month.time_series(january, end, Period::Month).unwrap();
+        assert_eq!(series, vec![1950, 0, 900, 0]);
+        assert!(matches!(
+            by_city.time_series(january, end, Period::Month),
+            Err(Error::InvalidGrouping(_))
+        ));
+
+        // the wine of march was paid in part by card
+        let march = PaymentId::new(FakeUtcTime::parse_str("2024/03/10 10:00").unwrap());
+        let split = PaymentDetail::new("Rome".into(), "Market".into(), "Cash".into())
+            .with_allocations(vec![
+                MethodAllocation::new("Cash".into(), Money::new(300)),
+                MethodAllocation::new("Card".into(), Money::new(600)),
+            ]);
+        all_payments.modify_payment(&march, split).unwrap();
+        let by_method = all_payments.query().group_by(&[GroupBy::Method]).unwrap();
+        assert_eq!(by_method.sums(), vec![600, 2250]);
+        let by_method = (all_payments.query().item("Wine".into()))
+            .group_by(&[GroupBy::Method, GroupBy::Item])
+            .unwrap();
+        assert_eq!(by_method.sums(), vec![600, 1500]);
+    }
+}
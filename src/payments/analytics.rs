@@ -0,0 +1,314 @@
+use super::{AllPayments, GroupBy};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcTime, Period},
+    types::money::Money,
+};
+use chrono::{Datelike, Timelike};
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+
+/// what a single figure of the statistics is
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Sample {
+    /// the total of each payment
+    Payment,
+    /// the spending of each day, or week, including those without spending;
+    /// partial weeks at the edges of the range count as whole
+    Period(Period),
+}
+
+/// descriptive statistics in the base currency
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+pub struct SpendingStats {
+    /// every figure in ascending order
+    samples: Vec<Money>,
+    /// rounded half away from zero
+    mean: Money,
+    /// population standard deviation, rounded to the cent
+    std_dev: Money,
+}
+
+/// a run of consecutive days without spending, `start` included
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Streak {
+    start: FakeUtcTime,
+    days: u32,
+}
+
+impl SpendingStats {
+    pub(super) fn new(mut samples: Vec<Money>) -> Result<Self> {
+        samples.sort_unstable();
+        if samples.is_empty() {
+            return Ok(Self::default());
+        }
+        let count = i64::try_from(samples.len()).map_err(|_| Error::MoneyOverflow)?;
+        let sum = Money::checked_sum(samples.iter().copied())?;
+        let exact_mean = sum.cents() as f64 / count as f64;
+        let variance = (samples.iter())
+            .map(|sample| (sample.cents() as f64 - exact_mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        Ok(Self {
+            samples,
+            mean: sum.checked_scale(1, count)?,
+            std_dev: Money::new(variance.sqrt().round() as i64),
+        })
+    }
+
+    /// linear interpolation between the closest ranks rounded to the cent,
+    /// `percent` is clamped to 0..=100 and an empty sample gives zero
+    pub fn percentile(&self, percent: f64) -> Money {
+        let Some(last) = self.samples.len().checked_sub(1) else {
+            return Money::default();
+        };
+        let rank = percent.clamp(0.0, 100.0) / 100.0 * last as f64;
+        let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+        let (low_value, high_value) = (
+            self.samples[low].cents() as f64,
+            self.samples[high].cents() as f64,
+        );
+        Money::new((low_value + (high_value - low_value) * (rank - low as f64)).round() as i64)
+    }
+
+    pub fn median(&self) -> Money {
+        self.percentile(50.0)
+    }
+
+    pub fn min(&self) -> Money {
+        self.samples.first().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Money {
+        self.samples.last().copied().unwrap_or_default()
+    }
+}
+
+impl AllPayments {
+    /// statistics of the spending from `start` (included) to `end` (excluded)
+    /// in the base currency
+    pub fn spending_stats(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+        sample: Sample,
+    ) -> Result<SpendingStats> {
+        let query = self.query().between(start, end);
+        let samples = match sample {
+            Sample::Payment => (query.amounts())
+                .map(|amount| amount.map(|(_, _, amount)| amount))
+                .collect::<Result<_>>()?,
+            Sample::Period(period) => {
+                let group = GroupBy::from(period);
+                let series = (query.group_by(&[group])?).time_series(start, end, period)?;
+                series.into_iter().map(Money::new).collect()
+            }
+        };
+        SpendingStats::new(samples)
+    }
+
+    /// the longest run of days without spending, the earliest one on ties; a
+    /// day counts as spending when it has any payment with a positive total,
+    /// so a refund does not undo a purchase of the same day and a day with
+    /// only refunds is still without spending
+    pub fn longest_no_spend_streak(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<Option<Streak>> {
+        let days = self.spending_days(start, end)?;
+        let (mut longest, mut current): (Option<Streak>, Option<Streak>) = (None, None);
+        for (day, spent) in days {
+            current = match (spent, current) {
+                (false, Some(streak)) => Some(Streak {
+                    days: streak.days + 1,
+                    ..streak
+                }),
+                (false, None) => Some(Streak {
+                    start: day,
+                    days: 1,
+                }),
+                (true, _) => None,
+            };
+            if let Some(streak) = current
+                && longest.is_none_or(|longest| streak.days > longest.days)
+            {
+                longest = Some(streak);
+            }
+        }
+        Ok(longest)
+    }
+
+    /// total spending for each weekday, monday first
+    pub fn spending_by_weekday(&self, start: FakeUtcTime, end: FakeUtcTime) -> Result<[Money; 7]> {
+        let mut weekdays = [Money::default(); 7];
+        for payment in self.query().between(start, end).amounts() {
+            let (payid, _, amount) = payment?;
+            let weekday = payid.date().get_fields()?.weekday();
+            let total = &mut weekdays[weekday.num_days_from_monday() as usize];
+            *total = total.checked_add(amount)?;
+        }
+        Ok(weekdays)
+    }
+
+    /// total spending for each hour of the day, midnight first
+    pub fn spending_by_hour(&self, start: FakeUtcTime, end: FakeUtcTime) -> Result<[Money; 24]> {
+        let mut hours = [Money::default(); 24];
+        for payment in self.query().between(start, end).amounts() {
+            let (payid, _, amount) = payment?;
+            let total = &mut hours[payid.date().get_fields()?.hour() as usize];
+            *total = total.checked_add(amount)?;
+        }
+        Ok(hours)
+    }
+
+    /// every day of the range by its start, with whether it has a payment
+    /// with a positive total
+    fn spending_days(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<FakeUtcTime, bool>> {
+        let mut days = BTreeMap::new();
+        let mut day = start.period_start(Period::Day)?;
+        while day < end {
+            days.insert(day, false);
+            day = day.add_days(1)?;
+        }
+        for payment in self.query().between(start, end).amounts() {
+            let (payid, _, amount) = payment?;
+            if amount > Money::default() {
+                days.insert(payid.date().period_start(Period::Day)?, true);
+            }
+        }
+        Ok(days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sample;
+    use crate::{
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        time::{FakeUtcTime, Period},
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn spending_stats() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        // monday to wednesday, then a gap until sunday
+        let payments = [
+            ("2024/01/01 08:00", 100),
+            ("2024/01/01 19:00", 300),
+            ("2024/01/02 08:00", 200),
+            ("2024/01/03 19:00", 600),
+            ("2024/01/07 08:00", 800),
+        ];
+        for (date, price) in payments {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new("Rome".into(), "Market".into(), "Cash".into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            let orderdetail = OrderDetail::new(Money::new(price), 1);
+            (all_payments.add_order(&payid, OrderId::new("Bread".into()), orderdetail)).unwrap();
+        }
+        let start = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let end = FakeUtcTime::parse_str("2024/01/08 00:00").unwrap();
+
+        let per_payment = all_payments
+            .spending_stats(start, end, Sample::Payment)
+            .unwrap();
+        let cents = |samples: &[Money]| samples.iter().map(Money::cents).collect::<Vec<_>>();
+        assert_eq!(cents(per_payment.samples()), vec![100, 200, 300, 600, 800]);
+        assert_eq!(
+            (per_payment.mean().cents(), per_payment.median().cents()),
+            (400, 300)
+        );
+        assert_eq!(per_payment.percentile(75.0).cents(), 600);
+        assert_eq!(per_payment.percentile(90.0).cents(), 720);
+        assert_eq!(per_payment.std_dev().cents(), 261);
+
+        let daily = (all_payments.spending_stats(start, end, Sample::Period(Period::Day))).unwrap();
+        assert_eq!(cents(daily.samples()), vec![0, 0, 0, 200, 400, 600, 800]);
+        let (min, max, median) = (daily.min(), daily.max(), daily.median());
+        assert_eq!((min.cents(), max.cents(), median.cents()), (0, 800, 200));
+
+        let streak = all_payments
+            .longest_no_spend_streak(start, end)
+            .unwrap()
+            .unwrap();
+        let thursday = FakeUtcTime::parse_str("2024/01/04 00:00").unwrap();
+        assert_eq!((*streak.start(), *streak.days()), (thursday, 3));
+
+        let weekdays = all_payments.spending_by_weekday(start, end).unwrap();
+        assert_eq!(
+            weekdays.map(|total| total.cents()),
+            [400, 200, 600, 0, 0, 0, 800]
+        );
+        let hours = all_payments.spending_by_hour(start, end).unwrap();
+        assert_eq!((hours[8].cents(), hours[19].cents()), (1100, 900));
+
+        // a payment without orders is still a sample
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/07 20:00").unwrap());
+        let paydetail = PaymentDetail::new("Rome".into(), "Market".into(), "Cash".into());
+        all_payments.add_payment(payid, paydetail).unwrap();
+        let per_payment = all_payments
+            .spending_stats(start, end, Sample::Payment)
+            .unwrap();
+        assert_eq!(
+            cents(per_payment.samples()),
+            vec![0, 100, 200, 300, 600, 800]
+        );
+    }
+
+    #[test]
+    fn no_spend_streak_with_refunds() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        // a purchase refunded on tuesday, only a refund on wednesday
+        let payments = [
+            ("2024/01/01 08:00", 500),
+            ("2024/01/02 08:00", 500),
+            ("2024/01/02 19:00", -500),
+            ("2024/01/03 08:00", -300),
+        ];
+        for (date, price) in payments {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new("Rome".into(), "Market".into(), "Cash".into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            let orderdetail = OrderDetail::new(Money::new(price), 1);
+            (all_payments.add_order(&payid, OrderId::new("Bread".into()), orderdetail)).unwrap();
+        }
+        let day = |date| FakeUtcTime::parse_str(date).unwrap();
+
+        let tuesday =
+            all_payments.longest_no_spend_streak(day("2024/01/02 00:00"), day("2024/01/03 00:00"));
+        assert_eq!(tuesday.unwrap(), None);
+        let wednesday = day("2024/01/03 00:00");
+        let streak = all_payments
+            .longest_no_spend_streak(wednesday, day("2024/01/04 00:00"))
+            .unwrap()
+            .unwrap();
+        assert_eq!((*streak.start(), *streak.days()), (wednesday, 1));
+        let streak = all_payments
+            .longest_no_spend_streak(day("2024/01/01 00:00"), day("2024/01/08 00:00"))
+            .unwrap()
+            .unwrap();
+        assert_eq!((*streak.start(), *streak.days()), (wednesday, 5));
+    }
+}
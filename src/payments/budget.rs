@@ -0,0 +1,300 @@
+use super::{AllPayments, PayOrdersDetail, PaymentId, ValueSet};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcTime, Period, SECONDS_PER_DAY},
+    types::{internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+/// what the spending of a budget is made of
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetScope {
+    All,
+    /// orders whose item falls in the category or in one of its subcategories
+    Category(CustomString),
+    Shop(CustomString),
+    Method(CustomString),
+    City(CustomString),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BudgetPeriod {
+    Monthly,
+    Weekly,
+    /// from the first time (included) to the second (excluded)
+    Custom(FakeUtcTime, FakeUtcTime),
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct Budget {
+    scope: BudgetScope,
+    period: BudgetPeriod,
+    limit: Money,
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct BudgetStatus {
+    start: FakeUtcTime,
+    end: FakeUtcTime,
+    limit: Money,
+    spent: Money,
+    remaining: Money,
+    projected: Money,
+    overrun: Money,
+}
+
+impl BudgetScope {
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        let mut values = ValueSet::new();
+        match self {
+            BudgetScope::All => {}
+            BudgetScope::Category(category) => {
+                if !valid_values.categories.contains(category) {
+                    values.add_categories(vec![category.clone()]);
+                }
+            }
+            BudgetScope::Shop(shop) => {
+                if !valid_values.shops.contains(shop) {
+                    values.add_values(vec![], vec![shop.clone()], vec![], vec![]);
+                }
+            }
+            BudgetScope::Method(method) => {
+                if !valid_values.methods.contains(method) {
+                    values.add_values(vec![], vec![], vec![method.clone()], vec![]);
+                }
+            }
+            BudgetScope::City(city) => {
+                if !valid_values.cities.contains(city) {
+                    values.add_values(vec![city.clone()], vec![], vec![], vec![]);
+                }
+            }
+        }
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+}
+
+impl BudgetPeriod {
+    /// a custom period must end after it starts
+    pub fn is_valid(&self) -> bool {
+        match self {
+            BudgetPeriod::Monthly | BudgetPeriod::Weekly => true,
+            BudgetPeriod::Custom(start, end) => start < end,
+        }
+    }
+}
+
+impl Budget {
+    pub fn new(scope: BudgetScope, period: BudgetPeriod, limit: Money) -> Self {
+        Self {
+            scope,
+            period,
+            limit,
+        }
+    }
+
+    pub(super) fn scope_mut(&mut self) -> &mut BudgetScope {
+        &mut self.scope
+    }
+
+    /// bounds of the budget period containing `now`
+    pub fn window(&self, now: FakeUtcTime) -> Result<(FakeUtcTime, FakeUtcTime)> {
+        match self.period {
+            BudgetPeriod::Monthly => Ok((
+                now.period_start(Period::Month)?,
+                now.period_end(Period::Month)?,
+            )),
+            BudgetPeriod::Weekly => Ok((
+                now.period_start(Period::Week)?,
+                now.period_end(Period::Week)?,
+            )),
+            BudgetPeriod::Custom(start, end) => Ok((start, end)),
+        }
+    }
+}
+
+impl BudgetStatus {
+    /// limit spread evenly over the days of the period, in cents
+    pub fn daily_limit(&self) -> f64 {
+        let days = (self.end.timestamp() - self.start.timestamp()) as f64 / SECONDS_PER_DAY as f64;
+        self.limit.cents() as f64 / days.max(1.0)
+    }
+}
+
+impl AllPayments {
+    /// part of a payment that counts towards `scope`, in the base currency
+    pub fn scope_amount(
+        &self,
+        scope: &BudgetScope,
+        payid: &PaymentId,
+        payment: &PayOrdersDetail,
+    ) -> Result<Money> {
+        let details = &payment.payment_details;
+        let amount = match scope {
+            BudgetScope::All => payment.calcualte_total_price()?,
+            BudgetScope::Shop(shop) if shop == &details.shop => payment.calcualte_total_price()?,
+            BudgetScope::City(city) if city == &details.city => payment.calcualte_total_price()?,
+            BudgetScope::Method(method) => payment
+                .calculate_method_amounts(payid)?
+                .remove(method)
+                .unwrap_or_default(),
+            BudgetScope::Category(category) => {
+                let mut amount = Money::default();
+                for (orderid, price) in payment.calculate_order_prices()? {
+                    let in_category = self.categories.item_in_category(&orderid.item, category);
+                    if in_category {
+                        amount = amount.checked_add(price)?;
+                    }
+                }
+                amount
+            }
+            BudgetScope::Shop(_) | BudgetScope::City(_) => Money::default(),
+        };
+        self.to_base_currency(payid, details, amount)
+    }
+
+    pub fn add_budget(&mut self, name: CustomString, budget: Budget) -> Result<()> {
+        if self.budgets.contains_key(&name) {
+            return Err(Error::BudgetDuplicated(name));
+        }
+        if !budget.period.is_valid() {
+            return Err(Error::InvalidBudgetPeriod(budget.period));
+        }
+        budget.scope.check_missing_elements(&self.value_set)?;
+
+        assert!(self.budgets.insert(name, budget).is_none());
+
+        Ok(())
+    }
+
+    pub fn remove_budget(&mut self, name: &CustomString) -> Result<()> {
+        self.budgets
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error::BudgetNotFound(name.clone()))
+    }
+
+    /// spending of the budget period containing `now`; the projection
+    /// extends the spending rate so far to the whole period
+    pub fn budget_status(&self, name: &CustomString, now: FakeUtcTime) -> Result<BudgetStatus> {
+        let budget = self
+            .budgets
+            .get(name)
+            .ok_or_else(|| Error::BudgetNotFound(name.clone()))?;
+        let (start, end) = budget.window(now)?;
+        let mut spent = Money::default();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            spent = spent.checked_add(self.scope_amount(&budget.scope, payid, payment)?)?;
+        }
+
+        let length = end.timestamp() - start.timestamp();
+        let elapsed = (now.timestamp() - start.timestamp()).clamp(1, length.max(1));
+        let projected = spent.checked_scale(length.max(1), elapsed)?;
+        let overrun = projected.checked_sub(budget.limit)?.max(Money::default());
+        Ok(BudgetStatus {
+            start,
+            end,
+            limit: budget.limit,
+            spent,
+            remaining: budget.limit.checked_sub(spent)?,
+            projected,
+            overrun,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Budget, BudgetPeriod, BudgetScope};
+    use crate::{
+        error::Error,
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn budget_status() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market"), CustomString::from("Bar")],
+            vec![CustomString::from("Card")],
+            vec![CustomString::from("Bread"), CustomString::from("Coffee")],
+        );
+        values.add_categories(vec![CustomString::from("Food")]);
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        all_payments
+            .set_item_category("Bread".into(), "Food".into())
+            .unwrap();
+
+        for (date, shop, item, price) in [
+            ("2024/04/01 10:00", "Market", "Bread", 3000),
+            ("2024/04/05 10:00", "Bar", "Coffee", 1000),
+            ("2024/05/01 10:00", "Market", "Bread", 500),
+        ] {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new("Rome".into(), shop.into(), "Card".into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            let orderdetail = OrderDetail::new(Money::new(price), 1);
+            all_payments
+                .add_order(&payid, OrderId::new(item.into()), orderdetail)
+                .unwrap();
+        }
+
+        let monthly = Budget::new(BudgetScope::All, BudgetPeriod::Monthly, 10_000.into());
+        let food = Budget::new(
+            BudgetScope::Category("Food".into()),
+            BudgetPeriod::Weekly,
+            2_000.into(),
+        );
+        let missing = Budget::new(
+            BudgetScope::Shop("Pub".into()),
+            BudgetPeriod::Weekly,
+            2_000.into(),
+        );
+        all_payments.add_budget("Month".into(), monthly).unwrap();
+        all_payments.add_budget("Food".into(), food).unwrap();
+        assert!(matches!(
+            all_payments.add_budget("Pub".into(), missing),
+            Err(Error::MissingElements(_))
+        ));
+        let reversed = BudgetPeriod::Custom(
+            FakeUtcTime::parse_str("2024/05/01 00:00").unwrap(),
+            FakeUtcTime::parse_str("2024/04/01 00:00").unwrap(),
+        );
+        let reversed = Budget::new(BudgetScope::All, reversed, 2_000.into());
+        assert!(matches!(
+            all_payments.add_budget("Reversed".into(), reversed),
+            Err(Error::InvalidBudgetPeriod(_))
+        ));
+
+        // ten days into april, the spending rate leads to 12.00 in 30 days
+        let now = FakeUtcTime::parse_str("2024/04/11 00:00").unwrap();
+        let status = all_payments.budget_status(&"Month".into(), now).unwrap();
+        assert_eq!(status.spent(), &Money::new(4000));
+        assert_eq!(status.remaining(), &Money::new(6000));
+        assert_eq!(status.projected(), &Money::new(12_000));
+        assert_eq!(status.overrun(), &Money::new(2_000));
+        assert_eq!(status.daily_limit(), 10_000.0 / 30.0);
+
+        let now = FakeUtcTime::parse_str("2024/04/01 12:00").unwrap();
+        let status = all_payments.budget_status(&"Food".into(), now).unwrap();
+        assert_eq!(status.spent(), &Money::new(3000));
+        assert_eq!(status.remaining(), &Money::new(-1000));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
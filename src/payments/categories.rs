@@ -0,0 +1,310 @@
+use super::{AllPayments, PaymentId, ValueKind, ValueSet};
+use crate::{
+    error::{Error, Result},
+    time::FakeUtcTime,
+    types::{internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+
+/// categories without a parent are roots of the tree
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+pub struct CategoryTree {
+    parents: BTreeMap<CustomString, CustomString>,
+    items: BTreeMap<CustomString, CustomString>,
+    shops: BTreeMap<CustomString, CustomString>,
+}
+
+impl CategoryTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the category followed by all its ancestors, up to the root
+    pub fn ancestors(&self, category: &CustomString) -> Vec<CustomString> {
+        let mut ancestors = vec![category.clone()];
+        while let Some(parent) = self.parents.get(ancestors.last().unwrap_or(category)) {
+            if ancestors.contains(parent) {
+                break;
+            }
+            ancestors.push(parent.clone());
+        }
+        ancestors
+    }
+
+    /// whether the item belongs to `category` or to one of its subcategories
+    pub fn item_in_category(&self, item: &CustomString, category: &CustomString) -> bool {
+        self.items
+            .get(item)
+            .is_some_and(|item_category| self.ancestors(item_category).contains(category))
+    }
+
+    /// path from the root, as in `Food > Fruit`
+    pub fn path(&self, category: &CustomString) -> String {
+        let mut ancestors = self.ancestors(category);
+        ancestors.reverse();
+        let names: Vec<&str> = ancestors.iter().map(CustomString::as_str).collect();
+        names.join(" > ")
+    }
+
+    /// moves the category of a renamed item or shop, when merging into a
+    /// value that already has a category that one is kept
+    pub(super) fn replace_value(
+        &mut self,
+        kind: ValueKind,
+        from: &CustomString,
+        to: &CustomString,
+    ) {
+        let map = match kind {
+            ValueKind::Item => &mut self.items,
+            ValueKind::Shop => &mut self.shops,
+            ValueKind::City | ValueKind::Method => return,
+        };
+        if let Some(category) = map.remove(from) {
+            map.entry(to.clone()).or_insert(category);
+        }
+    }
+
+    /// forgets the category of a removed item or shop
+    pub(super) fn remove_value(&mut self, kind: ValueKind, value: &CustomString) {
+        match kind {
+            ValueKind::Item => self.items.remove(value),
+            ValueKind::Shop => self.shops.remove(value),
+            ValueKind::City | ValueKind::Method => None,
+        };
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        let mut values = ValueSet::new();
+        let categories = (self.parents.iter())
+            .flat_map(|(category, parent)| [category, parent])
+            .chain(self.items.values())
+            .chain(self.shops.values())
+            .filter(|category| !valid_values.categories.contains(*category));
+        values.add_categories(categories.cloned().collect::<Vec<_>>());
+        let items = (self.items.keys()).filter(|item| !valid_values.items.contains(*item));
+        let shops = (self.shops.keys()).filter(|shop| !valid_values.shops.contains(*shop));
+        values.add_values(
+            vec![],
+            shops.cloned().collect(),
+            vec![],
+            items.cloned().collect(),
+        );
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+}
+
+fn add_rolled_up(
+    totals: &mut BTreeMap<CustomString, Money>,
+    tree: &CategoryTree,
+    category: &CustomString,
+    amount: Money,
+) -> Result<()> {
+    for ancestor in tree.ancestors(category) {
+        let total = totals.entry(ancestor).or_default();
+        *total = total.checked_add(amount)?;
+    }
+    Ok(())
+}
+
+impl AllPayments {
+    /// moves `category` under `parent`, or makes it a root with `None`
+    pub fn set_category_parent(
+        &mut self,
+        category: CustomString,
+        parent: Option<CustomString>,
+    ) -> Result<()> {
+        let mut tree = self.categories.clone();
+        match parent {
+            Some(parent) => {
+                if tree.ancestors(&parent).contains(&category) {
+                    return Err(Error::CategoryCycle(category));
+                }
+                tree.parents.insert(category, parent);
+            }
+            None => {
+                tree.parents.remove(&category);
+            }
+        }
+        tree.check_missing_elements(&self.value_set)?;
+        self.categories = tree;
+        Ok(())
+    }
+
+    pub fn set_item_category(&mut self, item: CustomString, category: CustomString) -> Result<()> {
+        let mut tree = self.categories.clone();
+        tree.items.insert(item, category);
+        tree.check_missing_elements(&self.value_set)?;
+        self.categories = tree;
+        Ok(())
+    }
+
+    pub fn set_shop_category(&mut self, shop: CustomString, category: CustomString) -> Result<()> {
+        let mut tree = self.categories.clone();
+        tree.shops.insert(shop, category);
+        tree.check_missing_elements(&self.value_set)?;
+        self.categories = tree;
+        Ok(())
+    }
+
+    pub fn clear_item_category(&mut self, item: &CustomString) {
+        self.categories.items.remove(item);
+    }
+
+    pub fn clear_shop_category(&mut self, shop: &CustomString) {
+        self.categories.shops.remove(shop);
+    }
+
+    /// spending per item category from `start` (included) to `end`
+    /// (excluded), in the base currency, an end before the start selects
+    /// nothing; every category also includes its subcategories
+    pub fn item_category_totals(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<CustomString, Money>> {
+        let mut totals = BTreeMap::new();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            for (orderid, price) in payment.calculate_order_prices()? {
+                if let Some(category) = self.categories.items.get(&orderid.item) {
+                    let price = self.to_base_currency(payid, &payment.payment_details, price)?;
+                    add_rolled_up(&mut totals, &self.categories, category, price)?;
+                }
+            }
+        }
+        Ok(totals)
+    }
+
+    /// spending per shop category, like [`AllPayments::item_category_totals`]
+    pub fn shop_category_totals(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<CustomString, Money>> {
+        let mut totals = BTreeMap::new();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            let details = &payment.payment_details;
+            if let Some(category) = self.categories.shops.get(&details.shop) {
+                let total =
+                    self.to_base_currency(payid, details, payment.calcualte_total_price()?)?;
+                add_rolled_up(&mut totals, &self.categories, category, total)?;
+            }
+        }
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn category_totals() {
+        let (food, fruit, drinks, supermarket) = (
+            CustomString::from("Food"),
+            CustomString::from("Fruit"),
+            CustomString::from("Drinks"),
+            CustomString::from("Supermarket"),
+        );
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash")],
+            vec![
+                CustomString::from("Apple"),
+                CustomString::from("Bread"),
+                CustomString::from("Water"),
+            ],
+        );
+        values.add_categories(vec![
+            food.clone(),
+            fruit.clone(),
+            drinks.clone(),
+            supermarket.clone(),
+        ]);
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        all_payments
+            .set_category_parent(fruit.clone(), Some(food.clone()))
+            .unwrap();
+        assert!(matches!(
+            all_payments.set_category_parent(food.clone(), Some(fruit.clone())),
+            Err(Error::CategoryCycle(_))
+        ));
+        assert!(matches!(
+            all_payments.set_category_parent(food.clone(), Some("Stuff".into())),
+            Err(Error::MissingElements(_))
+        ));
+        assert!(matches!(
+            all_payments.set_item_category("Pear".into(), fruit.clone()),
+            Err(Error::MissingElements(_))
+        ));
+        all_payments
+            .set_item_category("Apple".into(), fruit.clone())
+            .unwrap();
+        all_payments
+            .set_item_category("Bread".into(), food.clone())
+            .unwrap();
+        all_payments
+            .set_item_category("Water".into(), drinks.clone())
+            .unwrap();
+        all_payments
+            .set_shop_category("Market".into(), supermarket.clone())
+            .unwrap();
+        assert_eq!(all_payments.categories().path(&fruit), "Food > Fruit");
+
+        let payid = PaymentId::new(0.into());
+        let paydetail = PaymentDetail::new("Rome".into(), "Market".into(), "Cash".into());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        for (item, price) in [("Apple", 120), ("Bread", 200), ("Water", 50)] {
+            let orderdetail = OrderDetail::new(Money::new(price), 1);
+            all_payments
+                .add_order(&payid, OrderId::new(item.into()), orderdetail)
+                .unwrap();
+        }
+
+        let totals = all_payments
+            .item_category_totals(0.into(), 60.into())
+            .unwrap();
+        assert_eq!(totals[&fruit], Money::new(120));
+        assert_eq!(totals[&food], Money::new(320));
+        assert_eq!(totals[&drinks], Money::new(50));
+        let totals = all_payments
+            .shop_category_totals(0.into(), 60.into())
+            .unwrap();
+        assert_eq!(totals[&supermarket], Money::new(370));
+        assert!(
+            all_payments
+                .item_category_totals(60.into(), 0.into())
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            all_payments
+                .shop_category_totals(60.into(), 0.into())
+                .unwrap()
+                .is_empty()
+        );
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
@@ -0,0 +1,227 @@
+use super::{AllPayments, PaymentId, RecurringLink, SpendingStats};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcTime, Period, SECONDS_PER_DAY},
+    types::money::Money,
+};
+use chrono::Months;
+use derive_getters::Getters;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ForecastMethod {
+    /// the average daily spending so far, carried over the remaining days
+    RunRate,
+    /// the spending on the same days one year earlier
+    LastYear,
+}
+
+/// spending projected to the end of a period in the base currency; every
+/// remaining day gets a band of one standard deviation of the daily spending
+/// the projection is based on, never below the recurring charges due that
+/// day, and the band of the total is the spending so far plus the daily
+/// bands, so the two always add up
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct Forecast {
+    /// spending from the start to the end of the current day
+    spent: Money,
+    /// recurring charges due before the end and not entered yet
+    recurring: Money,
+    /// expected spending of each remaining day in cents, recurring charges
+    /// included
+    daily: Vec<i64>,
+    /// band of each remaining day in cents
+    daily_low: Vec<i64>,
+    daily_high: Vec<i64>,
+    projected: Money,
+    /// `spent` plus the sum of `daily_low`, never below what is known
+    low: Money,
+    /// `spent` plus the sum of `daily_high`
+    high: Money,
+}
+
+fn year_before(time: FakeUtcTime) -> Result<FakeUtcTime> {
+    (time.get_fields()?.checked_sub_months(Months::new(12)))
+        .map(FakeUtcTime::from_fields)
+        .ok_or(Error::TimeFormatFailed(time))
+}
+
+/// whole days from `start` to `end`, a partial last day counts as one
+fn days_between(start: FakeUtcTime, end: FakeUtcTime) -> usize {
+    let seconds = (end.timestamp() - start.timestamp()).max(0);
+    seconds.div_euclid(SECONDS_PER_DAY) as usize + usize::from(seconds % SECONDS_PER_DAY != 0)
+}
+
+fn day_index(first_day: FakeUtcTime, time: FakeUtcTime) -> usize {
+    ((time.timestamp() - first_day.timestamp()) / SECONDS_PER_DAY) as usize
+}
+
+impl AllPayments {
+    /// projects the spending from `start` (included) to `end` (excluded),
+    /// the days up to the one of `now` are taken as they are
+    pub fn forecast(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+        now: FakeUtcTime,
+        method: ForecastMethod,
+    ) -> Result<Forecast> {
+        let tomorrow = now.period_end(Period::Day)?.clamp(start, end.max(start));
+        let remaining = days_between(tomorrow, end);
+        let spent = self.query().between(start, tomorrow).total()?;
+
+        let (stats, mut daily) = match method {
+            ForecastMethod::RunRate => {
+                let elapsed = days_between(start, tomorrow);
+                let stats = SpendingStats::new(self.everyday_spending(start, elapsed)?)?;
+                let mean = *stats.mean();
+                (stats, vec![mean; remaining])
+            }
+            ForecastMethod::LastYear => {
+                let samples = self.everyday_spending(year_before(tomorrow)?, remaining)?;
+                (SpendingStats::new(samples.clone())?, samples)
+            }
+        };
+
+        let mut recurring = Money::default();
+        let mut due = vec![Money::default(); remaining];
+        let links = self.recurring_links();
+        for (name, template) in &self.recurring {
+            let payment = template.payment();
+            for date in template.occurrences(end)? {
+                let materialized = template
+                    .last_materialized()
+                    .is_some_and(|last| date <= last)
+                    || links.contains(&RecurringLink::new(name.clone(), date));
+                if materialized || date < tomorrow || date >= end {
+                    continue;
+                }
+                let total = payment.calcualte_total_price()?;
+                let amount =
+                    self.to_base_currency(&PaymentId::new(date), payment.payment_details(), total)?;
+                recurring = recurring.checked_add(amount)?;
+                let day = &mut due[day_index(tomorrow, date).min(remaining - 1)];
+                *day = day.checked_add(amount)?;
+            }
+        }
+        for (day, due) in daily.iter_mut().zip(&due) {
+            *day = day.checked_add(*due)?;
+        }
+
+        let projected = spent.checked_add(Money::checked_sum(daily.iter().copied())?)?;
+        let mut daily_low = Vec::with_capacity(remaining);
+        let mut daily_high = Vec::with_capacity(remaining);
+        for (day, due) in daily.iter().zip(due) {
+            daily_low.push(day.checked_sub(*stats.std_dev())?.max(due));
+            daily_high.push(day.checked_add(*stats.std_dev())?);
+        }
+        let low = spent.checked_add(Money::checked_sum(daily_low.iter().copied())?)?;
+        let high = spent.checked_add(Money::checked_sum(daily_high.iter().copied())?)?;
+        Ok(Forecast {
+            spent,
+            recurring,
+            daily: daily.iter().map(Money::cents).collect(),
+            daily_low: daily_low.iter().map(Money::cents).collect(),
+            daily_high: daily_high.iter().map(Money::cents).collect(),
+            projected,
+            low,
+            high,
+        })
+    }
+
+    /// spending of `days` days from the day of `start` on, leaving out the
+    /// payments entered for a recurring template, which are forecast on their own
+    fn everyday_spending(&self, start: FakeUtcTime, days: usize) -> Result<Vec<Money>> {
+        let first_day = start.period_start(Period::Day)?;
+        let end = first_day.add_days(days as i64)?;
+        let mut daily = vec![Money::default(); days];
+        for payment in self.query().between(first_day, end).amounts() {
+            let (payid, payment, amount) = payment?;
+            if payment.recurring().is_some() {
+                continue;
+            }
+            let day = &mut daily[day_index(first_day, *payid.date())];
+            *day = day.checked_add(amount)?;
+        }
+        Ok(daily)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ForecastMethod;
+    use crate::{
+        payments::{
+            AllPayments, OrderDetail, OrderId, PayOrdersDetail, PaymentDetail, PaymentId,
+            Recurrence, RecurringTemplate, ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn forecast() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Milan")],
+            vec![CustomString::from("Market"), CustomString::from("Landlord")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread"), CustomString::from("Rent")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let mut spend = |date: &str, price| {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new("Milan".into(), "Market".into(), "Cash".into());
+            let orderdetail = OrderDetail::new(Money::new(price), 1);
+            let payment = PayOrdersDetail::new(paydetail)
+                .with_order(OrderId::new("Bread".into()), orderdetail);
+            all_payments.insert_payment(payid, payment).unwrap();
+        };
+        for day in 1..=10 {
+            spend(&format!("2024/01/{day:02} 09:00"), 1000);
+        }
+        spend("2023/01/15 09:00", 500);
+
+        let paydetail = PaymentDetail::new("Milan".into(), "Landlord".into(), "Cash".into());
+        let rent = OrderDetail::new(Money::new(5000), 1);
+        let payment = PayOrdersDetail::new(paydetail).with_order(OrderId::new("Rent".into()), rent);
+        let start = FakeUtcTime::parse_str("2023/12/20 09:00").unwrap();
+        let template = RecurringTemplate::new(start, Recurrence::MonthlyOnDay(20), payment);
+        all_payments.add_recurring("Rent".into(), template).unwrap();
+
+        let start = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let end = FakeUtcTime::parse_str("2024/02/01 00:00").unwrap();
+        let now = FakeUtcTime::parse_str("2024/01/10 18:00").unwrap();
+        let run_rate = (all_payments.forecast(start, end, now, ForecastMethod::RunRate)).unwrap();
+        assert_eq!(run_rate.daily().len(), 21);
+        assert_eq!((run_rate.daily()[0], run_rate.daily()[9]), (1000, 6000));
+        assert_eq!(run_rate.spent().cents(), 10_000);
+        assert_eq!(run_rate.recurring().cents(), 5000);
+        assert_eq!(run_rate.projected().cents(), 36_000);
+        assert_eq!(
+            (run_rate.low(), run_rate.high()),
+            (run_rate.projected(), run_rate.projected())
+        );
+        assert_eq!(run_rate.daily_low(), run_rate.daily());
+        assert_eq!(run_rate.daily_high(), run_rate.daily());
+
+        let last_year = (all_payments.forecast(start, end, now, ForecastMethod::LastYear)).unwrap();
+        assert_eq!((last_year.daily()[4], last_year.daily()[9]), (500, 5000));
+        assert_eq!(last_year.projected().cents(), 15_500);
+        assert!(
+            last_year.low() < last_year.projected() && last_year.projected() < last_year.high()
+        );
+        assert!(last_year.low().cents() >= 15_000);
+        let std_dev = last_year.daily_high()[0] - last_year.daily()[0];
+        assert!(std_dev > 0);
+        // the band never goes below the rent due on the 20th
+        assert_eq!(last_year.daily_low()[9], 5000);
+        assert_eq!(last_year.daily_high()[9], 5000 + std_dev);
+        // the total band is the sum of the daily ones
+        let spent = last_year.spent().cents();
+        let low: i64 = last_year.daily_low().iter().sum();
+        let high: i64 = last_year.daily_high().iter().sum();
+        assert_eq!(last_year.low().cents(), spent + low);
+        assert_eq!(last_year.high().cents(), spent + high);
+    }
+}
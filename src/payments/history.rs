@@ -0,0 +1,712 @@
+use super::{
+    Account, AccountTransfer, AllPayments, Budget, CategoryTree, IncomeDetail, IncomeId, Journal,
+    OrderDetail, OrderId, PayOrdersDetail, PaymentDetail, PaymentId, RecurringLink,
+    RecurringTemplate, TransferId, ValueKind, ValueSet,
+};
+use crate::{
+    attachments::AttachmentHash,
+    error::{Error, Result},
+    rates::ExchangeRates,
+    time::FakeUtcTime,
+    types::{currency::Currency, discount::Discount, internment::CustomString},
+};
+use std::collections::VecDeque;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// a mutation of `AllPayments`, applying one returns the command undoing it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Command {
+    AddValues(ValueSet),
+    /// fails if any of the values is still in use, see
+    /// `AllPayments::remove_values`
+    RemoveValues(ValueSet),
+    RemoveValue(ValueKind, CustomString),
+    /// the new name must not exist yet
+    RenameValue(ValueKind, CustomString, CustomString),
+    /// merges the first value into the second, existing one
+    MergeValues(ValueKind, CustomString, CustomString),
+    PruneUnusedValues,
+    AddPayment(PaymentId, PaymentDetail),
+    InsertPayment(PaymentId, PayOrdersDetail),
+    ModifyPayment(PaymentId, PaymentDetail),
+    ModifyPaymentDiscounts(PaymentId, Vec<Discount>),
+    RemovePayment(PaymentId),
+    AddOrder(PaymentId, OrderId, OrderDetail),
+    ModifyOrder(PaymentId, OrderId, OrderDetail),
+    RemoveOrder(PaymentId, OrderId),
+    SetPaymentNote(PaymentId, Option<String>),
+    SetOrderNote(PaymentId, OrderId, Option<String>),
+    Attach(PaymentId, AttachmentHash),
+    Detach(PaymentId, AttachmentHash),
+    AddIncome(IncomeId, IncomeDetail),
+    ModifyIncome(IncomeId, IncomeDetail),
+    RemoveIncome(IncomeId),
+    AddAccount(CustomString, Account),
+    RemoveAccount(CustomString),
+    AddTransfer(TransferId, AccountTransfer),
+    RemoveTransfer(TransferId),
+    AddRecurring(CustomString, RecurringTemplate),
+    RemoveRecurring(CustomString),
+    /// `None` unlinks the payment
+    LinkRecurring(PaymentId, Option<RecurringLink>),
+    /// enters the occurrences due up to the time, see
+    /// `AllPayments::materialize_recurring`
+    MaterializeRecurring(FakeUtcTime),
+    AddBudget(CustomString, Budget),
+    RemoveBudget(CustomString),
+    SetBaseCurrency(Currency),
+    SetExchangeRates(ExchangeRates),
+    SetCategoryParent(CustomString, Option<CustomString>),
+    /// `None` clears the category
+    SetItemCategory(CustomString, Option<CustomString>),
+    SetShopCategory(CustomString, Option<CustomString>),
+    /// applied all-or-nothing, see `AllPayments::apply_batch`
+    Batch(Vec<Command>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct HistoryEntry {
+    command: Command,
+    inverse: Command,
+}
+
+/// applied commands with their inverses, the oldest ones are dropped past
+/// `limit`; with a journal every command, undo and redo is also recorded
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct History {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    limit: usize,
+    journal: Option<Journal>,
+}
+
+impl AllPayments {
+    fn payment(&self, payid: &PaymentId) -> Result<&PayOrdersDetail> {
+        self.payments
+            .get(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))
+    }
+
+    fn order(&self, payid: &PaymentId, orderid: &OrderId) -> Result<&OrderDetail> {
+        self.payment(payid)?
+            .orders
+            .get(orderid)
+            .ok_or_else(|| Error::OrderNotFound(payid.clone(), orderid.clone()))
+    }
+
+    /// commands putting `from` and everything referring to it back as it is
+    /// now, undoing its merge into `into`
+    fn unmerge_commands(
+        &self,
+        kind: ValueKind,
+        from: &CustomString,
+        into: &CustomString,
+    ) -> Vec<Command> {
+        let references = (self.value_references(kind).remove(from)).unwrap_or_default();
+        let mut restore = vec![Command::AddValues(ValueSet::single(kind, from))];
+        let mut reinsert = vec![];
+        for transferid in references.transfers() {
+            restore.push(Command::RemoveTransfer(transferid.clone()));
+            let transfer = self.transfers[transferid].clone();
+            reinsert.push(Command::AddTransfer(transferid.clone(), transfer));
+        }
+        for incomeid in references.incomes() {
+            restore.push(Command::RemoveIncome(incomeid.clone()));
+            let income = self.incomes[incomeid].clone();
+            reinsert.push(Command::AddIncome(incomeid.clone(), income));
+        }
+        for name in references.budgets() {
+            restore.push(Command::RemoveBudget(name.clone()));
+            reinsert.push(Command::AddBudget(name.clone(), self.budgets[name].clone()));
+        }
+        for name in references.recurring() {
+            restore.push(Command::RemoveRecurring(name.clone()));
+            reinsert.push(Command::AddRecurring(
+                name.clone(),
+                self.recurring[name].clone(),
+            ));
+        }
+        for payid in references.payments() {
+            restore.push(Command::RemovePayment(payid.clone()));
+            reinsert.push(Command::InsertPayment(
+                payid.clone(),
+                self.payments[payid].clone(),
+            ));
+        }
+        // the account of `from` moved to `into`, which had none
+        if *references.account() {
+            restore.push(Command::RemoveAccount(into.clone()));
+            restore.push(Command::AddAccount(
+                from.clone(),
+                self.accounts[from].clone(),
+            ));
+        }
+        restore.extend(self.categories.category_commands(kind, [from, into]));
+        reinsert.reverse();
+        restore.extend(reinsert);
+        restore
+    }
+
+    /// applies the command and returns its inverse, nothing changes on error
+    pub fn apply(&mut self, command: Command) -> Result<Command> {
+        match command {
+            Command::AddValues(values) => {
+                let added = values.difference(&self.value_set);
+                self.add_values(values);
+                Ok(Command::RemoveValues(added))
+            }
+            Command::RemoveValues(values) => {
+                self.remove_values(&values)?;
+                Ok(Command::AddValues(values))
+            }
+            Command::RemoveValue(kind, value) => {
+                let mut restore = vec![Command::AddValues(ValueSet::single(kind, &value))];
+                restore.extend(self.categories.category_commands(kind, [&value]));
+                self.remove_value(kind, &value)?;
+                Ok(Command::Batch(restore))
+            }
+            Command::RenameValue(kind, from, to) => {
+                self.rename_value(kind, &from, to.clone())?;
+                Ok(Command::RenameValue(kind, to, from))
+            }
+            Command::MergeValues(kind, from, into) => {
+                let restore = self.unmerge_commands(kind, &from, &into);
+                self.merge_values(kind, &from, &into)?;
+                Ok(Command::Batch(restore))
+            }
+            Command::PruneUnusedValues => {
+                let categories = self.categories.clone();
+                let pruned = self.prune_unused_values();
+                let mut restore = vec![Command::AddValues(pruned.clone())];
+                for kind in ValueKind::ALL {
+                    restore.extend(categories.category_commands(kind, pruned.values(kind)));
+                }
+                Ok(Command::Batch(restore))
+            }
+            Command::AddPayment(payid, paydetail) => {
+                self.add_payment(payid.clone(), paydetail)?;
+                Ok(Command::RemovePayment(payid))
+            }
+            Command::InsertPayment(payid, payment) => {
+                self.insert_payment(payid.clone(), payment)?;
+                Ok(Command::RemovePayment(payid))
+            }
+            Command::ModifyPayment(payid, paydetail) => {
+                let old = self.payment(&payid)?.payment_details.clone();
+                self.modify_payment(&payid, paydetail)?;
+                Ok(Command::ModifyPayment(payid, old))
+            }
+            Command::ModifyPaymentDiscounts(payid, discounts) => {
+                let old = self.payment(&payid)?.discounts.clone();
+                self.modify_payment_discounts(&payid, discounts)?;
+                Ok(Command::ModifyPaymentDiscounts(payid, old))
+            }
+            Command::RemovePayment(payid) => {
+                let old = self.payment(&payid)?.clone();
+                self.remove_payment(&payid)?;
+                Ok(Command::InsertPayment(payid, old))
+            }
+            Command::AddOrder(payid, orderid, orderdetail) => {
+                self.add_order(&payid, orderid.clone(), orderdetail)?;
+                Ok(Command::RemoveOrder(payid, orderid))
+            }
+            Command::ModifyOrder(payid, orderid, orderdetail) => {
+                let old = self.order(&payid, &orderid)?.clone();
+                self.modify_order(&payid, &orderid, orderdetail)?;
+                Ok(Command::ModifyOrder(payid, orderid, old))
+            }
+            Command::RemoveOrder(payid, orderid) => {
+                let old = self.order(&payid, &orderid)?.clone();
+                self.remove_order(&payid, &orderid)?;
+                Ok(Command::AddOrder(payid, orderid, old))
+            }
+            Command::SetPaymentNote(payid, note) => {
+                let old = self.payment(&payid)?.note.clone();
+                self.set_payment_note(&payid, note)?;
+                Ok(Command::SetPaymentNote(payid, old))
+            }
+            Command::SetOrderNote(payid, orderid, note) => {
+                let old = self.order(&payid, &orderid)?.note.clone();
+                self.set_order_note(&payid, &orderid, note)?;
+                Ok(Command::SetOrderNote(payid, orderid, old))
+            }
+            Command::Attach(payid, hash) => {
+                let attached = self.payment(&payid)?.attachments.contains(&hash);
+                self.attach(&payid, hash)?;
+                Ok(match attached {
+                    true => Command::Batch(vec![]),
+                    false => Command::Detach(payid, hash),
+                })
+            }
+            Command::Detach(payid, hash) => {
+                self.detach(&payid, &hash)?;
+                Ok(Command::Attach(payid, hash))
+            }
+            Command::AddIncome(incomeid, income) => {
+                self.add_income(incomeid.clone(), income)?;
+                Ok(Command::RemoveIncome(incomeid))
+            }
+            Command::ModifyIncome(incomeid, income) => {
+                let old = (self.incomes.get(&incomeid).cloned())
+                    .ok_or_else(|| Error::IncomeNotFound(incomeid.clone()))?;
+                self.modify_income(&incomeid, income)?;
+                Ok(Command::ModifyIncome(incomeid, old))
+            }
+            Command::RemoveIncome(incomeid) => {
+                let old = (self.incomes.get(&incomeid).cloned())
+                    .ok_or_else(|| Error::IncomeNotFound(incomeid.clone()))?;
+                self.remove_income(&incomeid)?;
+                Ok(Command::AddIncome(incomeid, old))
+            }
+            Command::AddAccount(method, account) => {
+                self.add_account(method.clone(), account)?;
+                Ok(Command::RemoveAccount(method))
+            }
+            Command::RemoveAccount(method) => {
+                let old = (self.accounts.get(&method).cloned())
+                    .ok_or_else(|| Error::AccountNotFound(method.clone()))?;
+                self.remove_account(&method)?;
+                Ok(Command::AddAccount(method, old))
+            }
+            Command::AddTransfer(transferid, transfer) => {
+                self.add_transfer(transferid.clone(), transfer)?;
+                Ok(Command::RemoveTransfer(transferid))
+            }
+            Command::RemoveTransfer(transferid) => {
+                let old = (self.transfers.get(&transferid).cloned())
+                    .ok_or_else(|| Error::TransferNotFound(transferid.clone()))?;
+                self.remove_transfer(&transferid)?;
+                Ok(Command::AddTransfer(transferid, old))
+            }
+            Command::AddRecurring(name, template) => {
+                self.add_recurring(name.clone(), template)?;
+                Ok(Command::RemoveRecurring(name))
+            }
+            Command::RemoveRecurring(name) => {
+                let old = (self.recurring.get(&name).cloned())
+                    .ok_or_else(|| Error::RecurringNotFound(name.clone()))?;
+                self.remove_recurring(&name)?;
+                Ok(Command::AddRecurring(name, old))
+            }
+            Command::LinkRecurring(payid, link) => {
+                let old = self.payment(&payid)?.recurring.clone();
+                self.link_recurring(&payid, link)?;
+                Ok(Command::LinkRecurring(payid, old))
+            }
+            Command::MaterializeRecurring(until) => {
+                let templates = self.recurring.clone();
+                let added = self.materialize_recurring(until)?;
+                let mut restore: Vec<Command> =
+                    added.into_iter().map(Command::RemovePayment).collect();
+                for (name, template) in templates {
+                    if self.recurring.get(&name) != Some(&template) {
+                        restore.push(Command::RemoveRecurring(name.clone()));
+                        restore.push(Command::AddRecurring(name, template));
+                    }
+                }
+                Ok(Command::Batch(restore))
+            }
+            Command::AddBudget(name, budget) => {
+                self.add_budget(name.clone(), budget)?;
+                Ok(Command::RemoveBudget(name))
+            }
+            Command::RemoveBudget(name) => {
+                let old = (self.budgets.get(&name).cloned())
+                    .ok_or_else(|| Error::BudgetNotFound(name.clone()))?;
+                self.remove_budget(&name)?;
+                Ok(Command::AddBudget(name, old))
+            }
+            Command::SetBaseCurrency(currency) => {
+                let old = self.base_currency;
+                self.set_base_currency(currency);
+                Ok(Command::SetBaseCurrency(old))
+            }
+            Command::SetExchangeRates(rates) => {
+                let old = self.exchange_rates.clone();
+                self.set_exchange_rates(rates);
+                Ok(Command::SetExchangeRates(old))
+            }
+            Command::SetCategoryParent(category, parent) => {
+                let old = self.categories.parents().get(&category).cloned();
+                self.set_category_parent(category.clone(), parent)?;
+                Ok(Command::SetCategoryParent(category, old))
+            }
+            Command::SetItemCategory(item, category) => {
+                let old = self.categories.items().get(&item).cloned();
+                match category {
+                    Some(category) => self.set_item_category(item.clone(), category)?,
+                    None => self.clear_item_category(&item),
+                }
+                Ok(Command::SetItemCategory(item, old))
+            }
+            Command::SetShopCategory(shop, category) => {
+                let old = self.categories.shops().get(&shop).cloned();
+                match category {
+                    Some(category) => self.set_shop_category(shop.clone(), category)?,
+                    None => self.clear_shop_category(&shop),
+                }
+                Ok(Command::SetShopCategory(shop, old))
+            }
+            Command::Batch(commands) => self.apply_batch(commands).map(Command::Batch),
+        }
+    }
+
+    /// commands rebuilding the whole dataset from an empty one, exchange
+    /// rates excluded
+    pub fn to_commands(&self) -> Vec<Command> {
+        let mut commands = vec![
+            Command::AddValues(self.value_set.clone()),
+            Command::SetBaseCurrency(self.base_currency),
+        ];
+        for (category, parent) in self.categories.parents() {
+            commands.push(Command::SetCategoryParent(
+                category.clone(),
+                Some(parent.clone()),
+            ));
+        }
+        for (item, category) in self.categories.items() {
+            commands.push(Command::SetItemCategory(
+                item.clone(),
+                Some(category.clone()),
+            ));
+        }
+        for (shop, category) in self.categories.shops() {
+            commands.push(Command::SetShopCategory(
+                shop.clone(),
+                Some(category.clone()),
+            ));
+        }
+        for (payid, payment) in &self.payments {
+            commands.push(Command::InsertPayment(payid.clone(), payment.clone()));
+        }
+        for (incomeid, income) in &self.incomes {
+            commands.push(Command::AddIncome(incomeid.clone(), income.clone()));
+        }
+        for (method, account) in &self.accounts {
+            commands.push(Command::AddAccount(method.clone(), account.clone()));
+        }
+        for (transferid, transfer) in &self.transfers {
+            commands.push(Command::AddTransfer(transferid.clone(), transfer.clone()));
+        }
+        for (name, template) in &self.recurring {
+            commands.push(Command::AddRecurring(name.clone(), template.clone()));
+        }
+        for (name, budget) in &self.budgets {
+            commands.push(Command::AddBudget(name.clone(), budget.clone()));
+        }
+        commands
+    }
+
+    /// applies every command or none: all of them are tried, and on failure
+    /// the dataset is left untouched and `BatchFailed` lists each failing
+    /// command index with its error; a failure can make later commands that
+    /// depend on it fail too. Returns the inverse commands, in undo order
+    pub fn apply_batch(&mut self, commands: Vec<Command>) -> Result<Vec<Command>> {
+        let mut updated = self.clone();
+        let mut inverses = Vec::with_capacity(commands.len());
+        let mut errors = vec![];
+        for (index, command) in commands.into_iter().enumerate() {
+            match updated.apply(command) {
+                Ok(inverse) => inverses.push(inverse),
+                Err(error) => errors.push((index, error)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Error::BatchFailed(errors));
+        }
+        *self = updated;
+        inverses.reverse();
+        Ok(inverses)
+    }
+}
+
+impl CategoryTree {
+    /// commands setting the categories of `values` back to what they are now
+    fn category_commands<'a, Iter: IntoIterator<Item = &'a CustomString>>(
+        &self,
+        kind: ValueKind,
+        values: Iter,
+    ) -> Vec<Command> {
+        let (categories, command): (_, fn(_, _) -> Command) = match kind {
+            ValueKind::Item => (self.items(), Command::SetItemCategory),
+            ValueKind::Shop => (self.shops(), Command::SetShopCategory),
+            ValueKind::City | ValueKind::Method => return vec![],
+        };
+        (values.into_iter())
+            .map(|value| command(value.clone(), categories.get(value).cloned()))
+            .collect()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            limit,
+            journal: None,
+        }
+    }
+
+    /// records every change made through the history in the journal
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    fn apply(&self, all_payments: &mut AllPayments, command: Command) -> Result<Command> {
+        match &self.journal {
+            Some(journal) => journal.apply(all_payments, FakeUtcTime::now(), command),
+            None => all_payments.apply(command),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// applies the command and records it, which clears the redo stack
+    pub fn execute(&mut self, all_payments: &mut AllPayments, command: Command) -> Result<()> {
+        let inverse = self.apply(all_payments, command.clone())?;
+        self.undo.push_back(HistoryEntry { command, inverse });
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+        Ok(())
+    }
+
+    /// reverts the last command, `false` if there is nothing to undo
+    pub fn undo(&mut self, all_payments: &mut AllPayments) -> Result<bool> {
+        let Some(entry) = self.undo.pop_back() else {
+            return Ok(false);
+        };
+        match self.apply(all_payments, entry.inverse.clone()) {
+            Ok(command) => {
+                self.redo.push(HistoryEntry {
+                    command,
+                    inverse: entry.inverse,
+                });
+                Ok(true)
+            }
+            Err(error) => {
+                self.undo.push_back(entry);
+                Err(error)
+            }
+        }
+    }
+
+    /// applies again the last undone command, `false` if there is none
+    pub fn redo(&mut self, all_payments: &mut AllPayments) -> Result<bool> {
+        let Some(entry) = self.redo.pop() else {
+            return Ok(false);
+        };
+        match self.apply(all_payments, entry.command.clone()) {
+            Ok(inverse) => {
+                self.undo.push_back(HistoryEntry {
+                    command: entry.command,
+                    inverse,
+                });
+                Ok(true)
+            }
+            Err(error) => {
+                self.redo.push(entry);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, History};
+    use crate::{
+        error::Error,
+        payments::{
+            AllPayments, OrderDetail, OrderId, PayOrdersDetail, PaymentDetail, PaymentId,
+            Recurrence, RecurringTemplate, ValueKind, ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn undo_redo() {
+        let mut all_payments = AllPayments::new();
+        let mut history = History::new(3);
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        let bread = OrderId::new("Bread".into());
+
+        let mut states = vec![all_payments.clone()];
+        let commands = [
+            Command::AddValues(values),
+            Command::AddPayment(payid.clone(), paydetail),
+            Command::AddOrder(
+                payid.clone(),
+                bread.clone(),
+                OrderDetail::new(Money::new(300), 1),
+            ),
+            Command::ModifyOrder(
+                payid.clone(),
+                bread.clone(),
+                OrderDetail::new(Money::new(300), 2),
+            ),
+            Command::RemovePayment(payid.clone()),
+        ];
+        for command in commands {
+            history.execute(&mut all_payments, command).unwrap();
+            states.push(all_payments.clone());
+        }
+        assert!(
+            history
+                .execute(&mut all_payments, Command::RemovePayment(payid))
+                .is_err()
+        );
+
+        // only the last 3 commands are kept
+        for state in states[2..5].iter().rev() {
+            assert!(history.undo(&mut all_payments).unwrap());
+            assert_eq!(&all_payments, state);
+        }
+        assert!(!history.undo(&mut all_payments).unwrap());
+
+        assert!(history.redo(&mut all_payments).unwrap());
+        assert!(history.redo(&mut all_payments).unwrap());
+        assert_eq!(all_payments, states[4]);
+        history
+            .execute(
+                &mut all_payments,
+                Command::RemoveOrder(states[4].payments().keys().next().unwrap().clone(), bread),
+            )
+            .unwrap();
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn batch() {
+        let mut all_payments = AllPayments::new();
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        all_payments.add_values(values);
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        let order = |item: &str| {
+            let orderdetail = OrderDetail::new(Money::new(300), 1);
+            Command::AddOrder(payid.clone(), OrderId::new(item.into()), orderdetail)
+        };
+
+        let receipt = vec![
+            Command::AddPayment(payid.clone(), paydetail),
+            order("Bread"),
+            order("Cake"),
+            order("Bread"),
+        ];
+        let before = all_payments.clone();
+        match all_payments.apply_batch(receipt.clone()) {
+            Err(Error::BatchFailed(errors)) => {
+                let indices: Vec<usize> = errors.iter().map(|(index, _)| *index).collect();
+                assert_eq!(indices, vec![2, 3]);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert_eq!(all_payments, before);
+
+        let mut history = History::default();
+        let receipt = Command::Batch(receipt[..2].to_vec());
+        history.execute(&mut all_payments, receipt).unwrap();
+        assert_eq!(all_payments.payments()[&payid].orders().len(), 1);
+        assert!(history.undo(&mut all_payments).unwrap());
+        assert_eq!(all_payments, before);
+    }
+
+    #[test]
+    fn value_commands_undo() {
+        let mut all_payments = AllPayments::new();
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread"), CustomString::from("Cake")],
+        );
+        all_payments.add_values(values);
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        let bread = OrderId::new("Bread".into());
+        let payment = PayOrdersDetail::new(paydetail)
+            .with_order(bread.clone(), OrderDetail::new(Money::new(300), 1));
+        all_payments.insert_payment(payid, payment.clone()).unwrap();
+        let before = all_payments.clone();
+
+        // removing values in use fails without touching anything
+        let mut in_use = ValueSet::new();
+        in_use.add_values(vec![], vec![], vec![], vec![CustomString::from("Bread")]);
+        let mut history = History::default();
+        let result = history.execute(&mut all_payments, Command::RemoveValues(in_use));
+        assert!(matches!(result, Err(Error::ValueInUse(..))));
+        assert_eq!(all_payments, before);
+
+        let commands = [
+            Command::MergeValues(ValueKind::Item, "Bread".into(), "Cake".into()),
+            Command::RenameValue(ValueKind::Item, "Cake".into(), "Pie".into()),
+            Command::PruneUnusedValues,
+        ];
+        let mut states = vec![before];
+        for command in commands {
+            history.execute(&mut all_payments, command).unwrap();
+            states.push(all_payments.clone());
+        }
+        for state in states[..3].iter().rev() {
+            assert!(history.undo(&mut all_payments).unwrap());
+            assert_eq!(&all_payments, state);
+        }
+        while history.redo(&mut all_payments).unwrap() {}
+        assert_eq!(&all_payments, states.last().unwrap());
+
+        let pie = payment.orders()[&bread].clone();
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        let payment = PayOrdersDetail::new(paydetail).with_order(OrderId::new("Pie".into()), pie);
+        let start = FakeUtcTime::parse_str("2024/01/01 09:00").unwrap();
+        let template = RecurringTemplate::new(start, Recurrence::MonthlyOnDay(1), payment);
+        let mut cake = ValueSet::new();
+        cake.add_values(vec![], vec![], vec![], vec![CustomString::from("Cake")]);
+        let before = all_payments.clone();
+        let until = FakeUtcTime::parse_str("2024/03/15 00:00").unwrap();
+        let commands = vec![
+            Command::AddValues(cake),
+            Command::RemoveValue(ValueKind::Item, "Cake".into()),
+            Command::AddRecurring("Pie".into(), template),
+            Command::MaterializeRecurring(until),
+        ];
+        history
+            .execute(&mut all_payments, Command::Batch(commands))
+            .unwrap();
+        assert_eq!(all_payments.payments().len(), 4);
+        assert!(history.undo(&mut all_payments).unwrap());
+        assert_eq!(all_payments, before);
+    }
+}
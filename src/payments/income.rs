@@ -0,0 +1,270 @@
+use super::{AllPayments, PaymentId, ValueSet, next_id};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcTime, Period},
+    types::{currency::Currency, internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use std::{borrow::Borrow, collections::BTreeMap};
+
+/// incomes are identified by date and sequence number like payments, but
+/// with their own type so an income id is never passed where a payment id is
+/// expected; it still borrows as a `PaymentId`, so date ranges and `next_id`
+/// work the same on incomes and payments
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct IncomeId(PaymentId);
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct IncomeDetail {
+    source: CustomString,
+    amount: Money,
+    /// payment method the money is paid into
+    account: CustomString,
+    currency: Option<Currency>,
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct CashFlow {
+    income: Money,
+    expenses: Money,
+    net: Money,
+}
+
+impl IncomeDetail {
+    pub fn new(source: CustomString, amount: Money, account: CustomString) -> Self {
+        Self {
+            source,
+            amount,
+            account,
+            currency: None,
+        }
+    }
+
+    /// amount is in `currency` instead of the dataset base currency
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    pub(super) fn replace_method(&mut self, from: &CustomString, to: &CustomString) {
+        if &self.account == from {
+            self.account = to.clone();
+        }
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        let mut values = ValueSet::new();
+        if !valid_values.sources.contains(&self.source) {
+            values.add_sources(vec![self.source.clone()]);
+        }
+        if !valid_values.methods.contains(&self.account) {
+            values.add_values(vec![], vec![], vec![self.account.clone()], vec![]);
+        }
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+}
+
+impl CashFlow {
+    fn add(&mut self, income: Money, expenses: Money) -> Result<()> {
+        self.income = self.income.checked_add(income)?;
+        self.expenses = self.expenses.checked_add(expenses)?;
+        self.net = self.income.checked_sub(self.expenses)?;
+        Ok(())
+    }
+}
+
+impl IncomeId {
+    pub fn new(date: FakeUtcTime) -> Self {
+        Self(PaymentId::new(date))
+    }
+
+    pub fn new_seq(date: FakeUtcTime, seq: u32) -> Self {
+        Self(PaymentId::new_seq(date, seq))
+    }
+
+    pub fn date(&self) -> &FakeUtcTime {
+        self.0.date()
+    }
+
+    pub fn seq(&self) -> &u32 {
+        self.0.seq()
+    }
+}
+
+impl Borrow<PaymentId> for IncomeId {
+    fn borrow(&self) -> &PaymentId {
+        &self.0
+    }
+}
+
+impl From<PaymentId> for IncomeId {
+    fn from(value: PaymentId) -> Self {
+        Self(value)
+    }
+}
+
+impl AllPayments {
+    pub fn next_income_id(&self, date: FakeUtcTime) -> Result<IncomeId> {
+        next_id(&self.incomes, date)
+    }
+
+    pub fn add_income(&mut self, incomeid: IncomeId, incomedetail: IncomeDetail) -> Result<()> {
+        if self.incomes.contains_key(&incomeid) {
+            return Err(Error::IncomeDuplicated(incomeid));
+        }
+        incomedetail.check_missing_elements(&self.value_set)?;
+
+        assert!(self.incomes.insert(incomeid, incomedetail).is_none());
+
+        Ok(())
+    }
+
+    pub fn modify_income(&mut self, incomeid: &IncomeId, incomedetail: IncomeDetail) -> Result<()> {
+        let incomedetail_mut = self
+            .incomes
+            .get_mut(incomeid)
+            .ok_or_else(|| Error::IncomeNotFound(incomeid.clone()))?;
+        incomedetail.check_missing_elements(&self.value_set)?;
+
+        *incomedetail_mut = incomedetail;
+
+        Ok(())
+    }
+
+    pub fn remove_income(&mut self, incomeid: &IncomeId) -> Result<()> {
+        self.incomes
+            .remove(incomeid)
+            .map(|_| ())
+            .ok_or_else(|| Error::IncomeNotFound(incomeid.clone()))
+    }
+
+    pub fn income_base(&self, incomeid: &IncomeId, incomedetail: &IncomeDetail) -> Result<Money> {
+        self.exchange_rates.convert(
+            incomedetail.amount,
+            incomedetail.currency.unwrap_or(self.base_currency),
+            self.base_currency,
+            *incomeid.date(),
+        )
+    }
+
+    /// income, expenses and net savings of every `period` from `start`
+    /// (included) to `end` (excluded), keyed by period start, in the base
+    /// currency; an end before the start selects nothing
+    pub fn cash_flow(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+        period: Period,
+    ) -> Result<BTreeMap<FakeUtcTime, CashFlow>> {
+        let mut flows = BTreeMap::new();
+        let mut period_start = start.period_start(period)?;
+        while period_start < end {
+            flows.insert(period_start, CashFlow::default());
+            period_start = period_start.period_end(period)?;
+        }
+
+        let incomes = IncomeId::new(start)..IncomeId::new(end.max(start));
+        for (incomeid, income) in self.incomes.range(incomes) {
+            let key = incomeid.date().period_start(period)?;
+            let amount = self.income_base(incomeid, income)?;
+            flows
+                .entry(key)
+                .or_default()
+                .add(amount, Money::default())?;
+        }
+        for (payid, payment) in self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)))
+        {
+            let key = payid.date.period_start(period)?;
+            let total = payment.calcualte_total_price()?;
+            let amount = self.to_base_currency(payid, &payment.payment_details, total)?;
+            flows
+                .entry(key)
+                .or_default()
+                .add(Money::default(), amount)?;
+        }
+        Ok(flows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IncomeDetail, IncomeId};
+    use crate::{
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        time::{FakeUtcTime, Period},
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn cash_flow() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Bank")],
+            vec![CustomString::from("Bread")],
+        );
+        values.add_sources(vec![CustomString::from("Salary")]);
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        let january = FakeUtcTime::parse_str("2024/01/27 09:00").unwrap();
+        let february = FakeUtcTime::parse_str("2024/02/27 09:00").unwrap();
+        let salary = IncomeDetail::new("Salary".into(), Money::new(200_000), "Bank".into());
+        let bonus = IncomeDetail::new("Bonus".into(), Money::new(50_000), "Bank".into());
+        assert!(
+            all_payments
+                .add_income(IncomeId::new(january), bonus)
+                .is_err()
+        );
+        all_payments
+            .add_income(IncomeId::new(january), salary.clone())
+            .unwrap();
+        let incomeid = all_payments.next_income_id(january).unwrap();
+        assert_eq!(incomeid, IncomeId::new_seq(january, 1));
+        all_payments.add_income(incomeid, salary.clone()).unwrap();
+        all_payments
+            .add_income(IncomeId::new(february), salary)
+            .unwrap();
+
+        let payid = PaymentId::new(february);
+        let paydetail = PaymentDetail::new("Rome".into(), "Market".into(), "Bank".into());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        let orderdetail = OrderDetail::new(Money::new(250_000), 1);
+        all_payments
+            .add_order(&payid, OrderId::new("Bread".into()), orderdetail)
+            .unwrap();
+
+        let start = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let end = FakeUtcTime::parse_str("2024/04/01 00:00").unwrap();
+        let flows = all_payments.cash_flow(end, start, Period::Month).unwrap();
+        assert!(flows.is_empty());
+        let flows = all_payments.cash_flow(start, end, Period::Month).unwrap();
+        let flows: Vec<(i64, i64, i64)> = flows
+            .values()
+            .map(|flow| {
+                let (income, expenses) = (flow.income().cents(), flow.expenses().cents());
+                (income, expenses, flow.net().cents())
+            })
+            .collect();
+        assert_eq!(
+            flows,
+            vec![
+                (400_000, 0, 400_000),
+                (200_000, 250_000, -50_000),
+                (0, 0, 0)
+            ]
+        );
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
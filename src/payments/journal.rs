@@ -0,0 +1,199 @@
+use super::{AllPayments, Command, json::JournalEventJson};
+use crate::{
+    crypto::{decrypt_str_hex, encrypt_str_hex},
+    error::Result,
+    fs::{append_file, last_byte, read_file, truncate_file},
+    time::FakeUtcTime,
+};
+use derive_getters::Getters;
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_EXTENSION: &str = "journal";
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct JournalEvent {
+    time: FakeUtcTime,
+    command: Command,
+}
+
+/// append-only file with one event per line, as JSON or, when the dataset is
+/// encrypted, as the hex of the encrypted JSON
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct Journal {
+    path: PathBuf,
+    key: Option<Vec<u8>>,
+}
+
+impl Journal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            key: None,
+        }
+    }
+
+    /// journal beside the snapshot, `payments.json` gets
+    /// `payments.json.journal`
+    pub fn next_to<P: AsRef<Path>>(snapshot: P) -> Self {
+        let mut path = snapshot.as_ref().as_os_str().to_owned();
+        path.push(".");
+        path.push(JOURNAL_EXTENSION);
+        Self::new(path)
+    }
+
+    /// encrypts the events with the dataset key
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.key = Some(key.to_vec());
+        self
+    }
+
+    /// appends the event, after dropping a last line torn by an interrupted
+    /// write
+    pub fn record(&self, time: FakeUtcTime, command: &Command) -> Result<()> {
+        let json = JournalEventJson::from_api(time, command)?.dump_json()?;
+        let line = match &self.key {
+            Some(key) => encrypt_str_hex(key, &json)?,
+            None => json,
+        };
+        if self.path.exists() && last_byte(&self.path)?.is_some_and(|byte| byte != b'\n') {
+            let contents = read_file(&self.path)?;
+            let len = (contents.iter().rposition(|byte| *byte == b'\n')).map_or(0, |end| end + 1);
+            truncate_file(&self.path, len as u64)?;
+        }
+        append_file(&self.path, format!("{line}\n").as_bytes())
+    }
+
+    /// applies the command and records it, if either fails the dataset is
+    /// left as it was and nothing is recorded
+    pub fn apply(
+        &self,
+        all_payments: &mut AllPayments,
+        time: FakeUtcTime,
+        command: Command,
+    ) -> Result<Command> {
+        let inverse = all_payments.apply(command.clone())?;
+        if let Err(error) = self.record(time, &command) {
+            all_payments.apply(inverse)?;
+            return Err(error);
+        }
+        Ok(inverse)
+    }
+
+    /// records the current dataset as a single event, to start journaling an
+    /// existing snapshot
+    pub fn record_snapshot(&self, time: FakeUtcTime, all_payments: &AllPayments) -> Result<()> {
+        self.record(time, &Command::Batch(all_payments.to_commands()))
+    }
+
+    /// every event in order, none if the file doesn't exist yet; lines not
+    /// ended by a newline were torn by an interrupted write and are skipped
+    pub fn events(&self) -> Result<Vec<JournalEvent>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let contents = String::from_utf8_lossy(&read_file(&self.path)?).into_owned();
+        let mut lines: Vec<&str> = contents.split('\n').collect();
+        lines.pop();
+        let mut events = vec![];
+        for line in lines.into_iter().filter(|line| !line.is_empty()) {
+            let json = match &self.key {
+                Some(key) => decrypt_str_hex(key, line)?,
+                None => line.to_string(),
+            };
+            let (time, command) = JournalEventJson::from_json(&json)?.to_api()?;
+            events.push(JournalEvent { time, command });
+        }
+        Ok(events)
+    }
+
+    /// the dataset rebuilt from the events timed up to `until` included, or
+    /// from all of them
+    pub fn replay(&self, until: Option<FakeUtcTime>) -> Result<AllPayments> {
+        let mut all_payments = AllPayments::new();
+        let events = self.events()?.into_iter();
+        for event in events.filter(|event| until.is_none_or(|until| event.time <= until)) {
+            all_payments.apply(event.command)?;
+        }
+        Ok(all_payments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Journal;
+    use crate::{
+        fs::append_file,
+        payments::{
+            AllPayments, Command, History, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+    use std::{env::temp_dir, fs::remove_file};
+
+    #[test]
+    fn journal_replay() {
+        let key = "12345678901234567890123456789012".as_bytes();
+        let path = temp_dir().join(format!("track-payments-{}.json", std::process::id()));
+        let journal = Journal::next_to(&path).with_key(key);
+
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Bakery")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bread")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let start = FakeUtcTime::parse_str("2024/01/10 09:00").unwrap();
+        journal.record_snapshot(start, &all_payments).unwrap();
+
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let bread = OrderId::new("Bread".into());
+        let paydetail = PaymentDetail::new("Rome".into(), "Bakery".into(), "Cash".into());
+        let commands = [
+            Command::AddPayment(payid.clone(), paydetail),
+            Command::AddOrder(
+                payid.clone(),
+                bread.clone(),
+                OrderDetail::new(Money::new(300), 1),
+            ),
+            Command::ModifyOrder(payid, bread, OrderDetail::new(Money::new(300), 2)),
+        ];
+        let mut states = vec![all_payments.clone()];
+        for (days, command) in (1..).zip(commands) {
+            let time = start.add_days(days).unwrap();
+            journal.apply(&mut all_payments, time, command).unwrap();
+            states.push(all_payments.clone());
+        }
+
+        assert_eq!(journal.events().unwrap().len(), 4);
+        assert_eq!(journal.replay(None).unwrap(), all_payments);
+        assert_eq!(journal.replay(Some(start)).unwrap(), states[0]);
+        assert_eq!(
+            journal.replay(Some(start.add_days(2).unwrap())).unwrap(),
+            states[2]
+        );
+
+        // the file can't be read without the key
+        assert!(Journal::next_to(&path).events().is_err());
+
+        // a torn last line is skipped and dropped by the next event
+        append_file(journal.path(), b"0123abc").unwrap();
+        assert_eq!(journal.events().unwrap().len(), 4);
+        let mut history = History::default().with_journal(journal.clone());
+        let command =
+            Command::RemovePayment(all_payments.payments().keys().next().unwrap().clone());
+        history.execute(&mut all_payments, command).unwrap();
+        assert!(history.undo(&mut all_payments).unwrap());
+        assert_eq!(journal.events().unwrap().len(), 6);
+        assert_eq!(journal.replay(None).unwrap(), all_payments);
+        // events recorded now don't hide the older ones from a past replay
+        assert_eq!(
+            journal.replay(Some(start.add_days(2).unwrap())).unwrap(),
+            states[2]
+        );
+        remove_file(journal.path()).unwrap();
+    }
+}
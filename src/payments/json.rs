@@ -1,11 +1,28 @@
-use super::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet};
+use super::{
+    Account, AccountTransfer, AllPayments, Budget, BudgetPeriod, BudgetScope, Command,
+    IncomeDetail, IncomeId, MethodAllocation, OrderDetail, OrderId, PayOrdersDetail, PaymentDetail,
+    PaymentId, Recurrence, RecurringLink, RecurringTemplate, Share, TransferId, ValueKind,
+    ValueSet,
+};
 use crate::{
+    attachments::AttachmentHash,
     error::{Error, Result},
+    rates::ExchangeRatesJson,
     time::FakeUtcTime,
-    types::internment::CustomString,
+    types::{
+        currency::Currency,
+        discount::Discount,
+        internment::CustomString,
+        money::Money,
+        quantity::{Quantity, Unit, milli_number},
+        tax::Tax,
+    },
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ValueSetJson {
@@ -14,36 +31,866 @@ pub struct ValueSetJson {
     #[serde(rename = "paymentMethods")]
     methods: BTreeSet<CustomString>,
     items: BTreeSet<CustomString>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    participants: BTreeSet<CustomString>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    categories: BTreeSet<CustomString>,
+    #[serde(
+        rename = "incomeSources",
+        default,
+        skip_serializing_if = "BTreeSet::is_empty"
+    )]
+    sources: BTreeSet<CustomString>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct CategoryTreeJson {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    parents: BTreeMap<CustomString, CustomString>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    items: BTreeMap<CustomString, CustomString>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    shops: BTreeMap<CustomString, CustomString>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OrderJson {
     item: CustomString,
     #[serde(rename = "unitPrice")]
-    unit_price: u32,
-    quantity: u32,
+    unit_price: Money,
+    /// thousandths of `unit` in memory, written as a decimal amount of
+    /// `unit` like 0.735
+    #[serde(with = "milli_number")]
+    quantity: u64,
+    #[serde(default, skip_serializing_if = "is_pieces")]
+    unit: Unit,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    discounts: Vec<Discount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tax: Option<Tax>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    share: Option<Share>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payer: Option<CustomString>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AllocationJson {
+    #[serde(rename = "paymentMethod")]
+    method: CustomString,
+    amount: Money,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PaymentJson {
     date: CustomString,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    seq: u32,
     city: CustomString,
     #[serde(rename = "paymentMethod")]
     method: CustomString,
     shop: CustomString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allocations: Vec<AllocationJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payer: Option<CustomString>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    share: Option<Share>,
     orders: Vec<OrderJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    discounts: Vec<Discount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    attachments: BTreeSet<AttachmentHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurring: Option<RecurringLinkJson>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecurringLinkJson {
+    template: CustomString,
+    occurrence: CustomString,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IncomeJson {
+    date: CustomString,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    seq: u32,
+    source: CustomString,
+    amount: Money,
+    account: CustomString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountJson {
+    payment_method: CustomString,
+    opened: CustomString,
+    opening_balance: Money,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransferJson {
+    date: CustomString,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    seq: u32,
+    from: CustomString,
+    to: CustomString,
+    amount: Money,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecurringJson {
+    name: CustomString,
+    rule: Recurrence,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<CustomString>,
+    #[serde(
+        rename = "lastMaterialized",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    last: Option<CustomString>,
+    payment: PaymentJson,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetPeriodJson {
+    Monthly,
+    Weekly,
+    Custom {
+        start: CustomString,
+        end: CustomString,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BudgetJson {
+    name: CustomString,
+    scope: BudgetScope,
+    period: BudgetPeriodJson,
+    limit: Money,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PaymentIdJson {
+    date: CustomString,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    seq: u32,
+}
+
+/// a journal entry, see `Command`
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandJson {
+    AddValues(ValueSetJson),
+    RemoveValues(ValueSetJson),
+    RemoveValue {
+        kind: ValueKind,
+        value: CustomString,
+    },
+    RenameValue {
+        kind: ValueKind,
+        from: CustomString,
+        to: CustomString,
+    },
+    MergeValues {
+        kind: ValueKind,
+        from: CustomString,
+        into: CustomString,
+    },
+    PruneUnusedValues,
+    /// orders are ignored, only the payment details are added
+    AddPayment(PaymentJson),
+    InsertPayment(PaymentJson),
+    ModifyPayment(PaymentJson),
+    ModifyPaymentDiscounts {
+        id: PaymentIdJson,
+        discounts: Vec<Discount>,
+    },
+    RemovePayment(PaymentIdJson),
+    AddOrder {
+        id: PaymentIdJson,
+        order: OrderJson,
+    },
+    ModifyOrder {
+        id: PaymentIdJson,
+        order: OrderJson,
+    },
+    RemoveOrder {
+        id: PaymentIdJson,
+        item: CustomString,
+    },
+    SetPaymentNote {
+        id: PaymentIdJson,
+        note: Option<String>,
+    },
+    SetOrderNote {
+        id: PaymentIdJson,
+        item: CustomString,
+        note: Option<String>,
+    },
+    Attach {
+        id: PaymentIdJson,
+        attachment: AttachmentHash,
+    },
+    Detach {
+        id: PaymentIdJson,
+        attachment: AttachmentHash,
+    },
+    AddIncome(IncomeJson),
+    ModifyIncome(IncomeJson),
+    RemoveIncome(PaymentIdJson),
+    AddAccount(AccountJson),
+    RemoveAccount(CustomString),
+    AddTransfer(TransferJson),
+    RemoveTransfer(PaymentIdJson),
+    AddRecurring(RecurringJson),
+    RemoveRecurring(CustomString),
+    LinkRecurring {
+        id: PaymentIdJson,
+        recurring: Option<RecurringLinkJson>,
+    },
+    MaterializeRecurring(CustomString),
+    AddBudget(BudgetJson),
+    RemoveBudget(CustomString),
+    SetBaseCurrency(Currency),
+    SetExchangeRates(ExchangeRatesJson),
+    SetCategoryParent {
+        category: CustomString,
+        parent: Option<CustomString>,
+    },
+    SetItemCategory {
+        item: CustomString,
+        category: Option<CustomString>,
+    },
+    SetShopCategory {
+        shop: CustomString,
+        category: Option<CustomString>,
+    },
+    Batch(Vec<CommandJson>),
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JournalEventJson {
+    time: CustomString,
+    command: CommandJson,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AllPaymentsJson {
     #[serde(rename = "valueSet")]
     value_set: ValueSetJson,
+    #[serde(rename = "baseCurrency", default)]
+    base_currency: Currency,
+    #[serde(default, skip_serializing_if = "CategoryTreeJson::is_empty")]
+    categories: CategoryTreeJson,
     payments: Vec<PaymentJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recurring: Vec<RecurringJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    budgets: Vec<BudgetJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    incomes: Vec<IncomeJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    accounts: Vec<AccountJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    transfers: Vec<TransferJson>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+fn is_pieces(unit: &Unit) -> bool {
+    *unit == Unit::Pieces
+}
+
+impl CategoryTreeJson {
+    fn is_empty(&self) -> bool {
+        self.parents.is_empty() && self.items.is_empty() && self.shops.is_empty()
+    }
+}
+
+impl ValueSetJson {
+    fn from_api(values: &ValueSet) -> Self {
+        ValueSetJson {
+            cities: values.cities().clone(),
+            shops: values.shops().clone(),
+            methods: values.methods().clone(),
+            items: values.items().clone(),
+            participants: values.participants().clone(),
+            categories: values.categories().clone(),
+            sources: values.sources().clone(),
+        }
+    }
+
+    fn to_api(&self) -> ValueSet {
+        let mut values = ValueSet::new();
+        values.add_values(
+            self.cities.clone(),
+            self.shops.clone(),
+            self.methods.clone(),
+            self.items.clone(),
+        );
+        values.add_participants(self.participants.clone());
+        values.add_categories(self.categories.clone());
+        values.add_sources(self.sources.clone());
+        values
+    }
+}
+
+impl OrderJson {
+    fn from_api(orderid: &OrderId, order: &OrderDetail) -> Self {
+        OrderJson {
+            item: orderid.item().clone(),
+            unit_price: *order.unit_price(),
+            quantity: order.quantity().milli(),
+            unit: order.quantity().unit(),
+            discounts: order.discounts().clone(),
+            tax: *order.tax(),
+            share: order.share().clone(),
+            payer: order.payer().clone(),
+            note: order.note().clone(),
+        }
+    }
+
+    fn to_api(&self) -> (OrderId, OrderDetail) {
+        let quantity = Quantity::new(self.quantity, self.unit);
+        let mut order =
+            OrderDetail::new(self.unit_price, quantity).with_discounts(self.discounts.clone());
+        if let Some(tax) = self.tax {
+            order = order.with_tax(tax);
+        }
+        if let Some(share) = &self.share {
+            order = order.with_share(share.clone());
+        }
+        if let Some(payer) = &self.payer {
+            order = order.with_payer(payer.clone());
+        }
+        if let Some(note) = &self.note {
+            order = order.with_note(note.clone());
+        }
+        (OrderId::new(self.item.clone()), order)
+    }
+}
+
+impl PaymentJson {
+    fn from_api(payid: &PaymentId, payment_api: &PayOrdersDetail) -> Result<Self> {
+        let details = &payment_api.payment_details;
+        let allocations = details
+            .allocations()
+            .iter()
+            .map(|allocation| AllocationJson {
+                method: allocation.method().clone(),
+                amount: *allocation.amount(),
+            })
+            .collect();
+        let mut payment = PaymentJson {
+            date: payid.date().format_str()?.into(),
+            seq: *payid.seq(),
+            city: details.city().clone(),
+            shop: details.shop().clone(),
+            method: details.method().clone(),
+            currency: *details.currency(),
+            allocations,
+            payer: details.payer().clone(),
+            share: details.share().clone(),
+            orders: vec![],
+            discounts: payment_api.discounts().clone(),
+            note: payment_api.note().clone(),
+            attachments: payment_api.attachments().clone(),
+            recurring: None,
+        };
+        if let Some(link) = payment_api.recurring() {
+            payment.recurring = Some(RecurringLinkJson::from_api(link)?);
+        }
+
+        for (orderid, order) in payment_api.orders() {
+            payment.orders.push(OrderJson::from_api(orderid, order));
+        }
+
+        Ok(payment)
+    }
+
+    fn to_api(&self) -> Result<(PaymentId, PayOrdersDetail)> {
+        let date = FakeUtcTime::parse_str(self.date.as_str())?;
+        let payid = PaymentId::new_seq(date, self.seq);
+        let city = self.city.clone();
+        let shop = self.shop.clone();
+        let method = self.method.clone();
+        let mut paydetails = PaymentDetail::new(city, shop, method);
+        if let Some(currency) = self.currency {
+            paydetails = paydetails.with_currency(currency);
+        }
+        if !self.allocations.is_empty() {
+            let allocations = self
+                .allocations
+                .iter()
+                .map(|allocation| {
+                    MethodAllocation::new(allocation.method.clone(), allocation.amount)
+                })
+                .collect();
+            paydetails = paydetails.with_allocations(allocations);
+        }
+        if let Some(payer) = &self.payer {
+            paydetails = paydetails.with_payer(payer.clone());
+        }
+        if let Some(share) = &self.share {
+            paydetails = paydetails.with_share(share.clone());
+        }
+        let mut payment = PayOrdersDetail::new(paydetails)
+            .with_discounts(self.discounts.clone())
+            .with_attachments(self.attachments.clone());
+        if let Some(note) = &self.note {
+            payment = payment.with_note(note.clone());
+        }
+        if let Some(link) = &self.recurring {
+            payment = payment.with_recurring(link.to_api()?);
+        }
+
+        for order in &self.orders {
+            let (orderid, orderdetails) = order.to_api();
+            if payment.orders.contains_key(&orderid) {
+                return Err(Error::OrderDuplicated(payid, orderid));
+            }
+            payment = payment.with_order(orderid, orderdetails);
+        }
+
+        Ok((payid, payment))
+    }
+}
+
+impl RecurringLinkJson {
+    fn from_api(link: &RecurringLink) -> Result<Self> {
+        Ok(RecurringLinkJson {
+            template: link.template().clone(),
+            occurrence: link.occurrence().format_str()?.into(),
+        })
+    }
+
+    fn to_api(&self) -> Result<RecurringLink> {
+        let occurrence = FakeUtcTime::parse_str(self.occurrence.as_str())?;
+        Ok(RecurringLink::new(self.template.clone(), occurrence))
+    }
+}
+
+impl IncomeJson {
+    fn from_api(incomeid: &IncomeId, income: &IncomeDetail) -> Result<Self> {
+        Ok(IncomeJson {
+            date: incomeid.date().format_str()?.into(),
+            seq: *incomeid.seq(),
+            source: income.source().clone(),
+            amount: *income.amount(),
+            account: income.account().clone(),
+            currency: *income.currency(),
+        })
+    }
+
+    fn to_api(&self) -> Result<(IncomeId, IncomeDetail)> {
+        let date = FakeUtcTime::parse_str(self.date.as_str())?;
+        let incomeid = IncomeId::new_seq(date, self.seq);
+        let mut income = IncomeDetail::new(self.source.clone(), self.amount, self.account.clone());
+        if let Some(currency) = self.currency {
+            income = income.with_currency(currency);
+        }
+        Ok((incomeid, income))
+    }
+}
+
+impl AccountJson {
+    fn from_api(method: &CustomString, account: &Account) -> Result<Self> {
+        Ok(AccountJson {
+            payment_method: method.clone(),
+            opened: account.opened().format_str()?.into(),
+            opening_balance: *account.opening_balance(),
+            currency: *account.currency(),
+        })
+    }
+
+    fn to_api(&self) -> Result<(CustomString, Account)> {
+        let opened = FakeUtcTime::parse_str(self.opened.as_str())?;
+        let mut account = Account::new(opened, self.opening_balance);
+        if let Some(currency) = self.currency {
+            account = account.with_currency(currency);
+        }
+        Ok((self.payment_method.clone(), account))
+    }
+}
+
+impl TransferJson {
+    fn from_api(transferid: &TransferId, transfer: &AccountTransfer) -> Result<Self> {
+        Ok(TransferJson {
+            date: transferid.date().format_str()?.into(),
+            seq: *transferid.seq(),
+            from: transfer.from().clone(),
+            to: transfer.to().clone(),
+            amount: *transfer.amount(),
+        })
+    }
+
+    fn to_api(&self) -> Result<(TransferId, AccountTransfer)> {
+        let date = FakeUtcTime::parse_str(self.date.as_str())?;
+        let transfer = AccountTransfer::new(self.from.clone(), self.to.clone(), self.amount);
+        Ok((TransferId::new_seq(date, self.seq), transfer))
+    }
+}
+
+impl RecurringJson {
+    fn from_api(name: &CustomString, template: &RecurringTemplate) -> Result<Self> {
+        let payid = PaymentId::new(*template.start());
+        Ok(RecurringJson {
+            name: name.clone(),
+            rule: *template.rule(),
+            end: template
+                .end()
+                .map(|end| end.format_str())
+                .transpose()?
+                .map(Into::into),
+            last: (template.last_materialized())
+                .map(|last| last.format_str())
+                .transpose()?
+                .map(Into::into),
+            payment: PaymentJson::from_api(&payid, template.payment())?,
+        })
+    }
+
+    fn to_api(&self) -> Result<(CustomString, RecurringTemplate)> {
+        let (payid, payment) = self.payment.to_api()?;
+        let mut template = RecurringTemplate::new(*payid.date(), self.rule, payment);
+        if let Some(end) = &self.end {
+            template = template.with_end(FakeUtcTime::parse_str(end.as_str())?);
+        }
+        if let Some(last) = &self.last {
+            template = template.with_last_materialized(FakeUtcTime::parse_str(last.as_str())?);
+        }
+        Ok((self.name.clone(), template))
+    }
+}
+
+impl BudgetJson {
+    fn from_api(name: &CustomString, budget: &Budget) -> Result<Self> {
+        let period = match budget.period() {
+            BudgetPeriod::Monthly => BudgetPeriodJson::Monthly,
+            BudgetPeriod::Weekly => BudgetPeriodJson::Weekly,
+            BudgetPeriod::Custom(start, end) => BudgetPeriodJson::Custom {
+                start: start.format_str()?.into(),
+                end: end.format_str()?.into(),
+            },
+        };
+        Ok(BudgetJson {
+            name: name.clone(),
+            scope: budget.scope().clone(),
+            period,
+            limit: *budget.limit(),
+        })
+    }
+
+    fn to_api(&self) -> Result<(CustomString, Budget)> {
+        let period = match &self.period {
+            BudgetPeriodJson::Monthly => BudgetPeriod::Monthly,
+            BudgetPeriodJson::Weekly => BudgetPeriod::Weekly,
+            BudgetPeriodJson::Custom { start, end } => BudgetPeriod::Custom(
+                FakeUtcTime::parse_str(start.as_str())?,
+                FakeUtcTime::parse_str(end.as_str())?,
+            ),
+        };
+        if !period.is_valid() {
+            return Err(Error::InvalidBudgetPeriod(period));
+        }
+        let budget = Budget::new(self.scope.clone(), period, self.limit);
+        Ok((self.name.clone(), budget))
+    }
+}
+
+impl PaymentIdJson {
+    fn from_api(payid: &PaymentId) -> Result<Self> {
+        Ok(PaymentIdJson {
+            date: payid.date().format_str()?.into(),
+            seq: *payid.seq(),
+        })
+    }
+
+    fn to_api(&self) -> Result<PaymentId> {
+        let date = FakeUtcTime::parse_str(self.date.as_str())?;
+        Ok(PaymentId::new_seq(date, self.seq))
+    }
+}
+
+impl CommandJson {
+    pub fn from_api(command: &Command) -> Result<Self> {
+        let payment_only = |payid: &PaymentId, paydetail: &PaymentDetail| {
+            PaymentJson::from_api(payid, &PayOrdersDetail::new(paydetail.clone()))
+        };
+        Ok(match command {
+            Command::AddValues(values) => CommandJson::AddValues(ValueSetJson::from_api(values)),
+            Command::RemoveValues(values) => {
+                CommandJson::RemoveValues(ValueSetJson::from_api(values))
+            }
+            Command::RemoveValue(kind, value) => CommandJson::RemoveValue {
+                kind: *kind,
+                value: value.clone(),
+            },
+            Command::RenameValue(kind, from, to) => CommandJson::RenameValue {
+                kind: *kind,
+                from: from.clone(),
+                to: to.clone(),
+            },
+            Command::MergeValues(kind, from, into) => CommandJson::MergeValues {
+                kind: *kind,
+                from: from.clone(),
+                into: into.clone(),
+            },
+            Command::PruneUnusedValues => CommandJson::PruneUnusedValues,
+            Command::AddPayment(payid, paydetail) => {
+                CommandJson::AddPayment(payment_only(payid, paydetail)?)
+            }
+            Command::InsertPayment(payid, payment) => {
+                CommandJson::InsertPayment(PaymentJson::from_api(payid, payment)?)
+            }
+            Command::ModifyPayment(payid, paydetail) => {
+                CommandJson::ModifyPayment(payment_only(payid, paydetail)?)
+            }
+            Command::ModifyPaymentDiscounts(payid, discounts) => {
+                CommandJson::ModifyPaymentDiscounts {
+                    id: PaymentIdJson::from_api(payid)?,
+                    discounts: discounts.clone(),
+                }
+            }
+            Command::RemovePayment(payid) => {
+                CommandJson::RemovePayment(PaymentIdJson::from_api(payid)?)
+            }
+            Command::AddOrder(payid, orderid, order) => CommandJson::AddOrder {
+                id: PaymentIdJson::from_api(payid)?,
+                order: OrderJson::from_api(orderid, order),
+            },
+            Command::ModifyOrder(payid, orderid, order) => CommandJson::ModifyOrder {
+                id: PaymentIdJson::from_api(payid)?,
+                order: OrderJson::from_api(orderid, order),
+            },
+            Command::RemoveOrder(payid, orderid) => CommandJson::RemoveOrder {
+                id: PaymentIdJson::from_api(payid)?,
+                item: orderid.item().clone(),
+            },
+            Command::SetPaymentNote(payid, note) => CommandJson::SetPaymentNote {
+                id: PaymentIdJson::from_api(payid)?,
+                note: note.clone(),
+            },
+            Command::SetOrderNote(payid, orderid, note) => CommandJson::SetOrderNote {
+                id: PaymentIdJson::from_api(payid)?,
+                item: orderid.item().clone(),
+                note: note.clone(),
+            },
+            Command::Attach(payid, hash) => CommandJson::Attach {
+                id: PaymentIdJson::from_api(payid)?,
+                attachment: *hash,
+            },
+            Command::Detach(payid, hash) => CommandJson::Detach {
+                id: PaymentIdJson::from_api(payid)?,
+                attachment: *hash,
+            },
+            Command::AddIncome(incomeid, income) => {
+                CommandJson::AddIncome(IncomeJson::from_api(incomeid, income)?)
+            }
+            Command::ModifyIncome(incomeid, income) => {
+                CommandJson::ModifyIncome(IncomeJson::from_api(incomeid, income)?)
+            }
+            Command::RemoveIncome(incomeid) => {
+                CommandJson::RemoveIncome(PaymentIdJson::from_api(incomeid.borrow())?)
+            }
+            Command::AddAccount(method, account) => {
+                CommandJson::AddAccount(AccountJson::from_api(method, account)?)
+            }
+            Command::RemoveAccount(method) => CommandJson::RemoveAccount(method.clone()),
+            Command::AddTransfer(transferid, transfer) => {
+                CommandJson::AddTransfer(TransferJson::from_api(transferid, transfer)?)
+            }
+            Command::RemoveTransfer(transferid) => {
+                CommandJson::RemoveTransfer(PaymentIdJson::from_api(transferid)?)
+            }
+            Command::AddRecurring(name, template) => {
+                CommandJson::AddRecurring(RecurringJson::from_api(name, template)?)
+            }
+            Command::RemoveRecurring(name) => CommandJson::RemoveRecurring(name.clone()),
+            Command::LinkRecurring(payid, link) => CommandJson::LinkRecurring {
+                id: PaymentIdJson::from_api(payid)?,
+                recurring: link.as_ref().map(RecurringLinkJson::from_api).transpose()?,
+            },
+            Command::MaterializeRecurring(until) => {
+                CommandJson::MaterializeRecurring(until.format_str()?.into())
+            }
+            Command::AddBudget(name, budget) => {
+                CommandJson::AddBudget(BudgetJson::from_api(name, budget)?)
+            }
+            Command::RemoveBudget(name) => CommandJson::RemoveBudget(name.clone()),
+            Command::SetBaseCurrency(currency) => CommandJson::SetBaseCurrency(*currency),
+            Command::SetExchangeRates(rates) => {
+                CommandJson::SetExchangeRates(ExchangeRatesJson::from_api(rates)?)
+            }
+            Command::SetCategoryParent(category, parent) => CommandJson::SetCategoryParent {
+                category: category.clone(),
+                parent: parent.clone(),
+            },
+            Command::SetItemCategory(item, category) => CommandJson::SetItemCategory {
+                item: item.clone(),
+                category: category.clone(),
+            },
+            Command::SetShopCategory(shop, category) => CommandJson::SetShopCategory {
+                shop: shop.clone(),
+                category: category.clone(),
+            },
+            Command::Batch(commands) => CommandJson::Batch(
+                commands
+                    .iter()
+                    .map(CommandJson::from_api)
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+
+    pub fn to_api(&self) -> Result<Command> {
+        Ok(match self {
+            CommandJson::AddValues(values) => Command::AddValues(values.to_api()),
+            CommandJson::RemoveValues(values) => Command::RemoveValues(values.to_api()),
+            CommandJson::RemoveValue { kind, value } => Command::RemoveValue(*kind, value.clone()),
+            CommandJson::RenameValue { kind, from, to } => {
+                Command::RenameValue(*kind, from.clone(), to.clone())
+            }
+            CommandJson::MergeValues { kind, from, into } => {
+                Command::MergeValues(*kind, from.clone(), into.clone())
+            }
+            CommandJson::PruneUnusedValues => Command::PruneUnusedValues,
+            CommandJson::AddPayment(payment) => {
+                let (payid, payment) = payment.to_api()?;
+                Command::AddPayment(payid, payment.payment_details)
+            }
+            CommandJson::InsertPayment(payment) => {
+                let (payid, payment) = payment.to_api()?;
+                Command::InsertPayment(payid, payment)
+            }
+            CommandJson::ModifyPayment(payment) => {
+                let (payid, payment) = payment.to_api()?;
+                Command::ModifyPayment(payid, payment.payment_details)
+            }
+            CommandJson::ModifyPaymentDiscounts { id, discounts } => {
+                Command::ModifyPaymentDiscounts(id.to_api()?, discounts.clone())
+            }
+            CommandJson::RemovePayment(id) => Command::RemovePayment(id.to_api()?),
+            CommandJson::AddOrder { id, order } => {
+                let (orderid, order) = order.to_api();
+                Command::AddOrder(id.to_api()?, orderid, order)
+            }
+            CommandJson::ModifyOrder { id, order } => {
+                let (orderid, order) = order.to_api();
+                Command::ModifyOrder(id.to_api()?, orderid, order)
+            }
+            CommandJson::RemoveOrder { id, item } => {
+                Command::RemoveOrder(id.to_api()?, OrderId::new(item.clone()))
+            }
+            CommandJson::AddIncome(income) => {
+                let (incomeid, income) = income.to_api()?;
+                Command::AddIncome(incomeid, income)
+            }
+            CommandJson::ModifyIncome(income) => {
+                let (incomeid, income) = income.to_api()?;
+                Command::ModifyIncome(incomeid, income)
+            }
+            CommandJson::RemoveIncome(id) => Command::RemoveIncome(id.to_api()?.into()),
+            CommandJson::SetPaymentNote { id, note } => {
+                Command::SetPaymentNote(id.to_api()?, note.clone())
+            }
+            CommandJson::SetOrderNote { id, item, note } => {
+                Command::SetOrderNote(id.to_api()?, OrderId::new(item.clone()), note.clone())
+            }
+            CommandJson::Attach { id, attachment } => Command::Attach(id.to_api()?, *attachment),
+            CommandJson::Detach { id, attachment } => Command::Detach(id.to_api()?, *attachment),
+            CommandJson::AddAccount(account) => {
+                let (method, account) = account.to_api()?;
+                Command::AddAccount(method, account)
+            }
+            CommandJson::RemoveAccount(method) => Command::RemoveAccount(method.clone()),
+            CommandJson::AddTransfer(transfer) => {
+                let (transferid, transfer) = transfer.to_api()?;
+                Command::AddTransfer(transferid, transfer)
+            }
+            CommandJson::RemoveTransfer(id) => Command::RemoveTransfer(id.to_api()?),
+            CommandJson::AddRecurring(template) => {
+                let (name, template) = template.to_api()?;
+                Command::AddRecurring(name, template)
+            }
+            CommandJson::RemoveRecurring(name) => Command::RemoveRecurring(name.clone()),
+            CommandJson::LinkRecurring { id, recurring } => Command::LinkRecurring(
+                id.to_api()?,
+                recurring
+                    .as_ref()
+                    .map(RecurringLinkJson::to_api)
+                    .transpose()?,
+            ),
+            CommandJson::MaterializeRecurring(until) => {
+                Command::MaterializeRecurring(FakeUtcTime::parse_str(until.as_str())?)
+            }
+            CommandJson::AddBudget(budget) => {
+                let (name, budget) = budget.to_api()?;
+                Command::AddBudget(name, budget)
+            }
+            CommandJson::RemoveBudget(name) => Command::RemoveBudget(name.clone()),
+            CommandJson::SetBaseCurrency(currency) => Command::SetBaseCurrency(*currency),
+            CommandJson::SetExchangeRates(rates) => Command::SetExchangeRates(rates.to_api()?),
+            CommandJson::SetCategoryParent { category, parent } => {
+                Command::SetCategoryParent(category.clone(), parent.clone())
+            }
+            CommandJson::SetItemCategory { item, category } => {
+                Command::SetItemCategory(item.clone(), category.clone())
+            }
+            CommandJson::SetShopCategory { shop, category } => {
+                Command::SetShopCategory(shop.clone(), category.clone())
+            }
+            CommandJson::Batch(commands) => Command::Batch(
+                commands
+                    .iter()
+                    .map(CommandJson::to_api)
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl JournalEventJson {
+    pub fn from_api(time: FakeUtcTime, command: &Command) -> Result<Self> {
+        Ok(JournalEventJson {
+            time: time.format_str()?.into(),
+            command: CommandJson::from_api(command)?,
+        })
+    }
+
+    pub fn to_api(&self) -> Result<(FakeUtcTime, Command)> {
+        let time = FakeUtcTime::parse_str(self.time.as_str())?;
+        Ok((time, self.command.to_api()?))
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self> {
+        serde_json::from_str(json_str).map_err(Error::JsonParseFailed)
+    }
+
+    pub fn dump_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(Error::JsonDumpFailed)
+    }
 }
 
 impl AllPaymentsJson {
     pub fn from_json(json_str: &str) -> Result<Self> {
-        serde_json::from_str(json_str).map_err(Error::JsonDumpFailed)
+        serde_json::from_str(json_str).map_err(Error::JsonParseFailed)
     }
 
     pub fn dump_json(&self, fmt: bool) -> Result<String> {
@@ -55,80 +902,122 @@ impl AllPaymentsJson {
     }
 
     pub fn from_api(self_api: &AllPayments) -> Result<Self> {
-        let values = ValueSetJson {
-            cities: self_api.value_set().cities().clone(),
-            shops: self_api.value_set().shops().clone(),
-            methods: self_api.value_set().methods().clone(),
-            items: self_api.value_set().items().clone(),
+        let values = ValueSetJson::from_api(self_api.value_set());
+        let categories = CategoryTreeJson {
+            parents: self_api.categories().parents().clone(),
+            items: self_api.categories().items().clone(),
+            shops: self_api.categories().shops().clone(),
         };
         let mut payments = vec![];
-        for payment_api in self_api.payments() {
-            let date = (*payment_api.0.date()).format_str()?;
-            let city = payment_api.1.payment_details.city().clone();
-            let shop = payment_api.1.payment_details.shop().clone();
-            let method = payment_api.1.payment_details.method().clone();
-            let mut payment = PaymentJson {
-                date: date.into(),
-                city,
-                shop,
-                method,
-                orders: vec![],
-            };
-
-            let orders = payment_api.1.orders();
-
-            for order_api in orders {
-                let item = order_api.0.item().clone();
-                let unit_price = *order_api.1.unit_price();
-                let quantity = *order_api.1.quantity();
-                let order = OrderJson {
-                    item,
-                    unit_price,
-                    quantity,
-                };
-
-                payment.orders.push(order);
-            }
+        for (payid, payment) in self_api.payments() {
+            payments.push(PaymentJson::from_api(payid, payment)?);
+        }
+        let mut recurring = vec![];
+        for (name, template) in self_api.recurring() {
+            recurring.push(RecurringJson::from_api(name, template)?);
+        }
 
-            payments.push(payment);
+        let mut incomes = vec![];
+        for (incomeid, income) in self_api.incomes() {
+            incomes.push(IncomeJson::from_api(incomeid, income)?);
+        }
+        let mut accounts = vec![];
+        for (method, account) in self_api.accounts() {
+            accounts.push(AccountJson::from_api(method, account)?);
+        }
+        let mut transfers = vec![];
+        for (transferid, transfer) in self_api.transfers() {
+            transfers.push(TransferJson::from_api(transferid, transfer)?);
+        }
+        let mut budgets = vec![];
+        for (name, budget) in self_api.budgets() {
+            budgets.push(BudgetJson::from_api(name, budget)?);
         }
 
         Ok(AllPaymentsJson {
             value_set: values,
+            base_currency: *self_api.base_currency(),
+            categories,
             payments,
+            recurring,
+            budgets,
+            incomes,
+            accounts,
+            transfers,
         })
     }
 
     pub fn to_api(&self) -> Result<AllPayments> {
         let mut all_payments_api = AllPayments::new();
-        let mut values_api = ValueSet::new();
-        values_api.add_values(
-            self.value_set.cities.clone(),
-            self.value_set.shops.clone(),
-            self.value_set.methods.clone(),
-            self.value_set.items.clone(),
-        );
+        let values_api = self.value_set.to_api();
         all_payments_api.add_values(values_api);
+        all_payments_api.set_base_currency(self.base_currency);
+
+        // every entry becomes one command of a single batch, so each bad
+        // entry is reported by section and index and nothing half built is
+        // ever returned
+        let mut entries = vec![];
+        let mut errors = vec![];
+        let mut push = |section, index, command: Result<Command>| match command {
+            Ok(command) => entries.push((section, index, command)),
+            Err(error) => errors.push((section, index, error)),
+        };
+        for (index, (category, parent)) in self.categories.parents.iter().enumerate() {
+            let command = Command::SetCategoryParent(category.clone(), Some(parent.clone()));
+            push("categories.parents", index, Ok(command));
+        }
+        for (index, (item, category)) in self.categories.items.iter().enumerate() {
+            let command = Command::SetItemCategory(item.clone(), Some(category.clone()));
+            push("categories.items", index, Ok(command));
+        }
+        for (index, (shop, category)) in self.categories.shops.iter().enumerate() {
+            let command = Command::SetShopCategory(shop.clone(), Some(category.clone()));
+            push("categories.shops", index, Ok(command));
+        }
+        for (index, payment) in self.payments.iter().enumerate() {
+            let command =
+                (payment.to_api()).map(|(id, payment)| Command::InsertPayment(id, payment));
+            push("payments", index, command);
+        }
+        for (index, income) in self.incomes.iter().enumerate() {
+            let command = (income.to_api()).map(|(id, income)| Command::AddIncome(id, income));
+            push("incomes", index, command);
+        }
+        for (index, account) in self.accounts.iter().enumerate() {
+            let command =
+                (account.to_api()).map(|(method, account)| Command::AddAccount(method, account));
+            push("accounts", index, command);
+        }
+        for (index, transfer) in self.transfers.iter().enumerate() {
+            let command =
+                (transfer.to_api()).map(|(id, transfer)| Command::AddTransfer(id, transfer));
+            push("transfers", index, command);
+        }
+        for (index, recurring) in self.recurring.iter().enumerate() {
+            let command =
+                (recurring.to_api()).map(|(name, template)| Command::AddRecurring(name, template));
+            push("recurring", index, command);
+        }
+        for (index, budget) in self.budgets.iter().enumerate() {
+            let command = (budget.to_api()).map(|(name, budget)| Command::AddBudget(name, budget));
+            push("budgets", index, command);
+        }
 
-        for payment in &self.payments {
-            let date = FakeUtcTime::parse_str(payment.date.as_str())?;
-            let payid = PaymentId::new(date);
-            let city = payment.city.clone();
-            let shop = payment.shop.clone();
-            let method = payment.method.clone();
-            let paydetails = PaymentDetail::new(city, shop, method);
-            all_payments_api.add_payment(payid, paydetails)?;
-
-            for order in &payment.orders {
-                let payid = PaymentId::new(date);
-                let item = order.item.clone();
-                let unitprice = order.unit_price;
-                let quantity = order.quantity;
-                let orderid = OrderId::new(item);
-                let orderdetails = OrderDetail::new(unitprice, quantity);
-                all_payments_api.add_order(&payid, orderid, orderdetails)?;
+        let (sources, commands): (Vec<_>, Vec<_>) = (entries.into_iter())
+            .map(|(section, index, command)| ((section, index), command))
+            .unzip();
+        if let Err(error) = all_payments_api.apply_batch(commands) {
+            let Error::BatchFailed(failed) = error else {
+                return Err(error);
+            };
+            for (position, error) in failed {
+                let (section, index) = sources[position];
+                errors.push((section, index, error));
             }
         }
+        if !errors.is_empty() {
+            return Err(Error::JsonEntriesFailed(errors));
+        }
         Ok(all_payments_api)
     }
 }
@@ -136,6 +1025,7 @@ impl AllPaymentsJson {
 #[cfg(test)]
 mod tests {
     use super::AllPaymentsJson;
+    use crate::{error::Error, types::quantity::Unit};
 
     #[test]
     fn allpayments_legacy_json() {
@@ -161,4 +1051,85 @@ mod tests {
 
         assert_eq!(all_payments2, all_payments3);
     }
+
+    #[test]
+    fn fractional_quantity_json() {
+        let json_string = r#"
+{ "valueSet": { "cities": ["Rome"], "shops": ["Market"], "paymentMethods": ["Cash"],
+    "items": ["Cheese", "Milk"] },
+  "payments": [
+    { "date": "2024/03/27 12:34", "city": "Rome", "paymentMethod": "Cash", "shop": "Market",
+      "orders": [ { "item": "Cheese", "unitPrice": 1890, "quantity": 0.735, "unit": "kg" },
+                  { "item": "Milk", "unitPrice": 150, "quantity": 500, "unit": "ml" } ] } ] }
+        "#;
+        let all_payments = AllPaymentsJson::from_json(json_string).unwrap();
+        let all_payment_api = all_payments.to_api().unwrap();
+        let total = all_payment_api.payments().values().next().unwrap();
+        // 13.89 of cheese and 750.00 of milk at 1.50 per ml
+        assert_eq!(total.calcualte_total_price().unwrap().cents(), 76_389);
+        let milk = total.orders().values().last().unwrap();
+        let (price, unit) = milk.normalised_unit_price().unwrap();
+        assert_eq!((price.cents(), unit), (150_000, Unit::Liters));
+
+        let dumped = AllPaymentsJson::from_api(&all_payment_api).unwrap();
+        assert!(
+            dumped
+                .dump_json(false)
+                .unwrap()
+                .contains(r#""quantity":0.735,"unit":"kg""#)
+        );
+        assert_eq!(all_payments, dumped);
+    }
+
+    #[test]
+    fn invalid_json_reports_every_error() {
+        let json_string = r#"
+{ "valueSet": { "cities": ["Rome"], "shops": ["Market"], "paymentMethods": ["Cash"],
+    "items": ["Milk"] },
+  "payments": [
+    { "date": "2024/03/27 12:34", "city": "Rome", "paymentMethod": "Cash", "shop": "Market",
+      "orders": [ { "item": "Bread", "unitPrice": 100, "quantity": 1 } ] },
+    { "date": "2024/03/28 12:34", "city": "Rome", "paymentMethod": "Cash", "shop": "Market",
+      "orders": [ { "item": "Milk", "unitPrice": 100, "quantity": 1 } ] },
+    { "date": "2024/03/29 12:34", "city": "Milan", "paymentMethod": "Cash", "shop": "Market",
+      "orders": [] } ],
+  "accounts": [ { "paymentMethod": "Cash", "opened": "yesterday", "openingBalance": 0 } ] }
+        "#;
+        let all_payments = AllPaymentsJson::from_json(json_string).unwrap();
+        match all_payments.to_api() {
+            Err(Error::JsonEntriesFailed(errors)) => {
+                let entries: Vec<_> = (errors.iter())
+                    .map(|(section, index, _)| (*section, *index))
+                    .collect();
+                assert_eq!(
+                    entries,
+                    vec![("accounts", 0), ("payments", 0), ("payments", 2)]
+                );
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn duplicated_order_json() {
+        let json_string = r#"
+{ "valueSet": { "cities": ["Rome"], "shops": ["Market"], "paymentMethods": ["Cash"],
+    "items": ["Milk"] },
+  "payments": [
+    { "date": "2024/03/27 12:34", "city": "Rome", "paymentMethod": "Cash", "shop": "Market",
+      "orders": [ { "item": "Milk", "unitPrice": 100, "quantity": 1 },
+                  { "item": "Milk", "unitPrice": 120, "quantity": 2 } ] } ] }
+        "#;
+        let all_payments = AllPaymentsJson::from_json(json_string).unwrap();
+        match all_payments.to_api() {
+            Err(Error::JsonEntriesFailed(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert!(matches!(
+                    errors[0],
+                    ("payments", 0, Error::OrderDuplicated(..))
+                ));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
 }
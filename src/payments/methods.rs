@@ -0,0 +1,223 @@
+use super::{AllPayments, PayOrdersDetail, PaymentId};
+use crate::{
+    error::{Error, Result},
+    time::FakeUtcTime,
+    types::{internment::CustomString, money::Money},
+};
+use std::collections::BTreeMap;
+
+impl PayOrdersDetail {
+    /// share of the total price paid with every method, in the payment
+    /// currency; allocations win over the payment method when there are any
+    pub fn calculate_method_amounts(
+        &self,
+        payid: &PaymentId,
+    ) -> Result<BTreeMap<CustomString, Money>> {
+        self.check_allocations(payid)?;
+        let mut amounts = BTreeMap::new();
+        if self.payment_details.allocations.is_empty() {
+            amounts.insert(
+                self.payment_details.method.clone(),
+                self.calcualte_total_price()?,
+            );
+            return Ok(amounts);
+        }
+        for allocation in &self.payment_details.allocations {
+            let amount: &mut Money = amounts.entry(allocation.method.clone()).or_default();
+            *amount = amount.checked_add(allocation.amount)?;
+        }
+        Ok(amounts)
+    }
+
+    /// `amount`, the payment or a part of it, divided between the methods
+    /// in proportion to what each of them paid; the shares add up to
+    /// `amount`, and when the allocations cancel out to a zero total the
+    /// methods share it evenly
+    pub fn split_by_method(
+        &self,
+        payid: &PaymentId,
+        amount: Money,
+    ) -> Result<Vec<(CustomString, Money)>> {
+        let methods = self.calculate_method_amounts(payid)?;
+        if methods.len() == 1 {
+            return Ok(methods.into_keys().map(|method| (method, amount)).collect());
+        }
+        let total = self.calcualte_total_price()?;
+        let count = methods.len() as i64;
+        let mut shares = Vec::with_capacity(methods.len());
+        let mut left = amount;
+        let last = methods.len() - 1;
+        for (index, (method, paid)) in methods.into_iter().enumerate() {
+            let share = match index == last {
+                true => left,
+                false if total.cents() == 0 => amount.checked_scale(1, count)?,
+                false => amount.checked_scale(paid.cents(), total.cents())?,
+            };
+            left = left.checked_sub(share)?;
+            shares.push((method, share));
+        }
+        Ok(shares)
+    }
+
+    pub fn check_allocations(&self, payid: &PaymentId) -> Result<()> {
+        if self.payment_details.allocations.is_empty() {
+            return Ok(());
+        }
+        let total = self.calcualte_total_price()?;
+        let allocated = Money::checked_sum(
+            self.payment_details
+                .allocations
+                .iter()
+                .map(|allocation| allocation.amount),
+        )?;
+        (total == allocated)
+            .then_some(())
+            .ok_or_else(|| Error::AllocationMismatch(payid.clone(), total, allocated))
+    }
+}
+
+impl AllPayments {
+    /// every mutator already keeps allocations matching the total, this checks
+    /// the whole dataset again
+    pub fn check_allocations(&self) -> Result<()> {
+        for (payid, payment) in &self.payments {
+            payment.check_allocations(payid)?;
+        }
+        Ok(())
+    }
+
+    /// amount paid with every method from `start` (included) to `end`
+    /// (excluded), in the base currency; an end before the start selects
+    /// nothing
+    pub fn method_totals(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<CustomString, Money>> {
+        let mut totals = BTreeMap::new();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            for (method, amount) in payment.calculate_method_amounts(payid)? {
+                let amount = self.to_base_currency(payid, &payment.payment_details, amount)?;
+                let total: &mut Money = totals.entry(method).or_default();
+                *total = total.checked_add(amount)?;
+            }
+        }
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        payments::{
+            AllPayments, MethodAllocation, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet,
+        },
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn split_payment() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Milan")],
+            vec![CustomString::from("Canteen")],
+            vec![CustomString::from("Card"), CustomString::from("Voucher")],
+            vec![CustomString::from("Lunch")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        let card = CustomString::from("Card");
+        let voucher = CustomString::from("Voucher");
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Milan"),
+            CustomString::from("Canteen"),
+            card.clone(),
+        );
+        let split = paydetail.clone().with_allocations(vec![
+            MethodAllocation::new(voucher.clone(), Money::new(800)),
+            MethodAllocation::new(card.clone(), Money::new(450)),
+        ]);
+        let bad_split = paydetail
+            .clone()
+            .with_allocations(vec![MethodAllocation::new("Cash".into(), 1250.into())]);
+        let payid = PaymentId::new(0.into());
+        let payid2 = PaymentId::new(60.into());
+        assert!(matches!(
+            all_payments.add_payment(payid.clone(), bad_split),
+            Err(Error::MissingElements(_))
+        ));
+        // a split payment gets its allocations once the orders add up
+        assert!(matches!(
+            all_payments.add_payment(payid.clone(), split.clone()),
+            Err(Error::AllocationMismatch(..))
+        ));
+        all_payments
+            .add_payment(payid.clone(), paydetail.clone())
+            .unwrap();
+        all_payments.add_payment(payid2.clone(), paydetail).unwrap();
+        let lunch = OrderId::new(CustomString::from("Lunch"));
+        let orderdetail = OrderDetail::new(Money::new(1250), 1);
+        all_payments
+            .add_order(&payid, lunch.clone(), orderdetail.clone())
+            .unwrap();
+        all_payments
+            .add_order(&payid2, lunch.clone(), orderdetail)
+            .unwrap();
+        all_payments.modify_payment(&payid, split).unwrap();
+        all_payments.check_allocations().unwrap();
+
+        let totals = all_payments.method_totals(0.into(), 120.into()).unwrap();
+        assert_eq!(totals[&voucher], Money::new(800));
+        assert_eq!(totals[&card], Money::new(1700));
+        assert!(
+            all_payments
+                .method_totals(120.into(), 0.into())
+                .unwrap()
+                .is_empty()
+        );
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+
+        // changes to the total of a split payment are refused and undone
+        let orderdetail = OrderDetail::new(Money::new(1300), 1);
+        assert!(matches!(
+            all_payments.modify_order(&payid, &lunch, orderdetail),
+            Err(Error::AllocationMismatch(..))
+        ));
+        assert!(all_payments.remove_order(&payid, &lunch).is_err());
+        assert_eq!(all_payments, all_payments2);
+
+        // allocations cancelling out leave a zero total, shared evenly
+        let payid3 = PaymentId::new(120.into());
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Milan"),
+            CustomString::from("Canteen"),
+            card.clone(),
+        );
+        all_payments
+            .add_payment(payid3.clone(), paydetail.clone())
+            .unwrap();
+        all_payments
+            .add_order(&payid3, lunch, OrderDetail::new(Money::new(0), 1))
+            .unwrap();
+        let cancelled = paydetail.with_allocations(vec![
+            MethodAllocation::new(card.clone(), Money::new(500)),
+            MethodAllocation::new(voucher.clone(), Money::new(-500)),
+        ]);
+        all_payments.modify_payment(&payid3, cancelled).unwrap();
+        let shares = all_payments.payments()[&payid3]
+            .split_by_method(&payid3, Money::new(101))
+            .unwrap();
+        assert_eq!(
+            shares,
+            vec![(card, Money::new(51)), (voucher, Money::new(50))]
+        );
+    }
+}
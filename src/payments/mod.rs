@@ -1,13 +1,58 @@
+mod accounts;
+mod aggregate;
+mod analytics;
+mod budget;
+mod categories;
+mod forecast;
+mod history;
+mod income;
+mod journal;
 mod json;
+mod methods;
+mod notes;
+mod prices;
+mod query;
+mod recurring;
+mod shares;
+mod tax;
+mod values;
 
 use crate::{
+    attachments::AttachmentHash,
     error::{Error, Result},
+    rates::ExchangeRates,
     time::FakeUtcTime,
-    types::internment::CustomString,
+    types::{
+        currency::Currency,
+        discount::{Discount, apply_discounts},
+        internment::CustomString,
+        money::Money,
+        quantity::{Quantity, Unit},
+        tax::Tax,
+    },
 };
 use derive_getters::Getters;
 use json::AllPaymentsJson;
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+pub use accounts::{Account, AccountTransfer, TransferId};
+pub use aggregate::{Aggregate, Aggregation, GroupBy, GroupKey};
+pub use analytics::{Sample, SpendingStats, Streak};
+pub use budget::{Budget, BudgetPeriod, BudgetScope, BudgetStatus};
+pub use categories::CategoryTree;
+pub use forecast::{Forecast, ForecastMethod};
+pub use history::{Command, DEFAULT_HISTORY_LIMIT, History};
+pub use income::{CashFlow, IncomeDetail, IncomeId};
+pub use journal::{JOURNAL_EXTENSION, Journal, JournalEvent};
+pub use prices::{PriceHistory, PricePoint, PriceStats, ShopCity};
+pub use query::{OrderLine, Query};
+pub use recurring::{Recurrence, RecurringLink, RecurringTemplate};
+pub use shares::{Share, Transfer};
+pub use tax::TaxReport;
+pub use values::{ValueKind, ValueReferences};
 
 #[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
 pub struct ValueSet {
@@ -15,6 +60,9 @@ pub struct ValueSet {
     shops: BTreeSet<CustomString>,
     methods: BTreeSet<CustomString>,
     items: BTreeSet<CustomString>,
+    participants: BTreeSet<CustomString>,
+    categories: BTreeSet<CustomString>,
+    sources: BTreeSet<CustomString>,
 }
 
 #[derive(Getters, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -24,19 +72,29 @@ pub struct OrderId {
 
 #[derive(Getters, Debug, PartialEq, Eq, Clone)]
 pub struct OrderDetail {
-    unit_price: u32,
-    quantity: u32,
+    unit_price: Money,
+    quantity: Quantity,
+    discounts: Vec<Discount>,
+    tax: Option<Tax>,
+    share: Option<Share>,
+    payer: Option<CustomString>,
+    note: Option<String>,
 }
 
 #[derive(Getters, Debug, PartialEq, Eq, Clone)]
 pub struct PayOrdersDetail {
     payment_details: PaymentDetail,
     orders: BTreeMap<OrderId, OrderDetail>,
+    discounts: Vec<Discount>,
+    note: Option<String>,
+    attachments: BTreeSet<AttachmentHash>,
+    recurring: Option<RecurringLink>,
 }
 
 #[derive(Getters, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct PaymentId {
     date: FakeUtcTime,
+    seq: u32,
 }
 
 #[derive(Getters, Debug, PartialEq, Eq, Clone)]
@@ -44,14 +102,62 @@ pub struct PaymentDetail {
     city: CustomString,
     shop: CustomString,
     method: CustomString,
+    currency: Option<Currency>,
+    allocations: Vec<MethodAllocation>,
+    payer: Option<CustomString>,
+    share: Option<Share>,
 }
 
-#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct MethodAllocation {
+    method: CustomString,
+    amount: Money,
+}
+
+/// equality leaves out the exchange rates, they are loaded from their own
+/// file and never saved with the payments
+#[derive(Getters, Debug, Clone, Default)]
 pub struct AllPayments {
     value_set: ValueSet,
     payments: BTreeMap<PaymentId, PayOrdersDetail>,
+    base_currency: Currency,
+    exchange_rates: ExchangeRates,
+    categories: CategoryTree,
+    recurring: BTreeMap<CustomString, RecurringTemplate>,
+    budgets: BTreeMap<CustomString, Budget>,
+    incomes: BTreeMap<IncomeId, IncomeDetail>,
+    accounts: BTreeMap<CustomString, Account>,
+    transfers: BTreeMap<TransferId, AccountTransfer>,
 }
 
+impl PartialEq for AllPayments {
+    fn eq(&self, other: &Self) -> bool {
+        let AllPayments {
+            value_set,
+            payments,
+            base_currency,
+            exchange_rates: _,
+            categories,
+            recurring,
+            budgets,
+            incomes,
+            accounts,
+            transfers,
+        } = self;
+        value_set == &other.value_set
+            && payments == &other.payments
+            && base_currency == &other.base_currency
+            && categories == &other.categories
+            && recurring == &other.recurring
+            && budgets == &other.budgets
+            && incomes == &other.incomes
+            && accounts == &other.accounts
+            && transfers == &other.transfers
+    }
+}
+
+impl Eq for AllPayments {}
+
 impl ValueSet {
     pub fn new() -> Self {
         Self::default()
@@ -70,8 +176,26 @@ impl ValueSet {
         self.items.extend(items);
     }
 
+    pub fn add_participants<Iter: IntoIterator<Item = CustomString>>(
+        &mut self,
+        participants: Iter,
+    ) {
+        self.participants.extend(participants);
+    }
+
+    pub fn add_categories<Iter: IntoIterator<Item = CustomString>>(&mut self, categories: Iter) {
+        self.categories.extend(categories);
+    }
+
+    pub fn add_sources<Iter: IntoIterator<Item = CustomString>>(&mut self, sources: Iter) {
+        self.sources.extend(sources);
+    }
+
     pub fn extend(&mut self, other: ValueSet) {
         self.add_values(other.cities, other.shops, other.methods, other.items);
+        self.add_participants(other.participants);
+        self.add_categories(other.categories);
+        self.add_sources(other.sources);
     }
 
     pub fn is_empty(&self) -> bool {
@@ -79,6 +203,9 @@ impl ValueSet {
             && self.shops.is_empty()
             && self.methods.is_empty()
             && self.items.is_empty()
+            && self.participants.is_empty()
+            && self.categories.is_empty()
+            && self.sources.is_empty()
     }
 }
 
@@ -96,6 +223,7 @@ impl OrderId {
             .is_empty()
             .then_some(())
             .ok_or(values)
+            .map_err(Box::new)
             .map_err(Error::MissingElements)
     }
 }
@@ -107,10 +235,82 @@ impl From<CustomString> for OrderId {
 }
 
 impl OrderDetail {
-    pub fn new(unit_price: u32, quantity: u32) -> Self {
+    /// `unit_price` is per piece, kg, l... of the quantity unit, a plain
+    /// count means pieces
+    pub fn new<Q: Into<Quantity>>(unit_price: Money, quantity: Q) -> Self {
         Self {
             unit_price,
-            quantity,
+            quantity: quantity.into(),
+            discounts: vec![],
+            tax: None,
+            share: None,
+            payer: None,
+            note: None,
+        }
+    }
+
+    pub fn with_discounts(mut self, discounts: Vec<Discount>) -> Self {
+        self.discounts = discounts;
+        self
+    }
+
+    pub fn with_tax(mut self, tax: Tax) -> Self {
+        self.tax = Some(tax);
+        self
+    }
+
+    /// shares this line differently from the rest of the payment
+    pub fn with_share(mut self, share: Share) -> Self {
+        self.share = Some(share);
+        self
+    }
+
+    /// someone else than the payment payer paid for this line
+    pub fn with_payer(mut self, payer: CustomString) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        if let Some(share) = &self.share {
+            share.check_missing_elements(valid_values)?;
+        }
+        let mut values = ValueSet::new();
+        let missing = (self.payer.iter())
+            .filter(|&participant| !valid_values.participants.contains(participant))
+            .cloned();
+        values.add_participants(missing);
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+
+    pub fn calculate_list_price(&self) -> Result<Money> {
+        self.quantity.price(self.unit_price)
+    }
+
+    /// unit price per kg, l or piece, so lines bought in g and kg compare
+    pub fn normalised_unit_price(&self) -> Result<(Money, Unit)> {
+        let (unit, factor) = self.quantity.unit().base();
+        let factor = i64::try_from(factor).map_err(|_| Error::MoneyOverflow)?;
+        Ok((self.unit_price.checked_scale(factor, 1)?, unit))
+    }
+
+    /// list price with the line discounts applied in order, plus the tax when
+    /// prices are tax-exclusive
+    pub fn calculate_price(&self) -> Result<Money> {
+        let price = apply_discounts(self.calculate_list_price()?, &self.discounts)?;
+        match self.tax {
+            Some(tax) => tax.gross(price),
+            None => Ok(price),
         }
     }
 }
@@ -120,14 +320,88 @@ impl PayOrdersDetail {
         Self {
             payment_details,
             orders: BTreeMap::new(),
+            discounts: vec![],
+            note: None,
+            attachments: BTreeSet::new(),
+            recurring: None,
+        }
+    }
+
+    pub fn with_order(mut self, orderid: OrderId, orderdetail: OrderDetail) -> Self {
+        self.orders.insert(orderid, orderdetail);
+        self
+    }
+
+    pub fn with_discounts(mut self, discounts: Vec<Discount>) -> Self {
+        self.discounts = discounts;
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn with_attachments(mut self, attachments: BTreeSet<AttachmentHash>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    pub fn with_recurring(mut self, link: RecurringLink) -> Self {
+        self.recurring = Some(link);
+        self
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        self.payment_details.check_missing_elements(valid_values)?;
+        for (orderid, orderdetail) in &self.orders {
+            orderid.check_missing_elements(valid_values)?;
+            orderdetail.check_missing_elements(valid_values)?;
         }
+        Ok(())
+    }
+
+    /// sum of the discounted order lines, before the payment discounts
+    pub fn calculate_subtotal_price(&self) -> Result<Money> {
+        self.orders.values().try_fold(Money::default(), |acc, det| {
+            acc.checked_add(det.calculate_price()?)
+        })
     }
 
-    pub fn calcualte_total_price(&self) -> u32 {
-        self.orders
-            .values()
-            .map(|det| det.unit_price * det.quantity)
-            .sum()
+    /// line discounts first, then payment discounts in order, every step
+    /// rounded half away from zero to the cent
+    pub fn calcualte_total_price(&self) -> Result<Money> {
+        apply_discounts(self.calculate_subtotal_price()?, &self.discounts)
+    }
+
+    /// price of every order, with the payment discounts spread proportionally
+    /// over the lines; rounding leftovers go to the largest line, so the
+    /// prices always add up to the total price
+    pub fn calculate_order_prices(&self) -> Result<BTreeMap<OrderId, Money>> {
+        let mut prices = BTreeMap::new();
+        for (orderid, orderdetail) in &self.orders {
+            prices.insert(orderid.clone(), orderdetail.calculate_price()?);
+        }
+        if self.discounts.is_empty() {
+            return Ok(prices);
+        }
+
+        let subtotal = self.calculate_subtotal_price()?;
+        let total = self.calcualte_total_price()?;
+        let mut allocated = Money::default();
+        if subtotal != Money::default() {
+            for price in prices.values_mut() {
+                *price = price.checked_scale(total.cents(), subtotal.cents())?;
+                allocated = allocated.checked_add(*price)?;
+            }
+        }
+        if let Some(largest) = prices
+            .values_mut()
+            .max_by_key(|price| price.cents().unsigned_abs())
+        {
+            *largest = largest.checked_add(total.checked_sub(allocated)?)?;
+        }
+        Ok(prices)
     }
 }
 
@@ -139,7 +413,25 @@ impl From<PaymentDetail> for PayOrdersDetail {
 
 impl PaymentId {
     pub fn new(date: FakeUtcTime) -> Self {
-        Self { date }
+        Self::new_seq(date, 0)
+    }
+
+    pub fn new_seq(date: FakeUtcTime, seq: u32) -> Self {
+        Self { date, seq }
+    }
+}
+
+/// first id at `date` not used in `ids` yet
+fn next_id<Id, V>(ids: &BTreeMap<Id, V>, date: FakeUtcTime) -> Result<Id>
+where
+    Id: Ord + Borrow<PaymentId> + From<PaymentId>,
+{
+    let same_date = PaymentId::new_seq(date, 0)..=PaymentId::new_seq(date, u32::MAX);
+    match ids.range::<PaymentId, _>(same_date).next_back() {
+        Some((last, _)) => (last.borrow().seq.checked_add(1))
+            .map(|seq| PaymentId::new_seq(date, seq).into())
+            .ok_or(Error::IdsExhausted(date)),
+        None => Ok(PaymentId::new(date).into()),
     }
 }
 
@@ -151,7 +443,40 @@ impl From<FakeUtcTime> for PaymentId {
 
 impl PaymentDetail {
     pub fn new(city: CustomString, shop: CustomString, method: CustomString) -> Self {
-        Self { city, shop, method }
+        Self {
+            city,
+            shop,
+            method,
+            currency: None,
+            allocations: vec![],
+            payer: None,
+            share: None,
+        }
+    }
+
+    /// amounts are in `currency` instead of the dataset base currency
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    /// splits the payment across several methods; the amounts must add up to
+    /// the total price and win over the payment method, which stays the main
+    /// method the payment is grouped under
+    pub fn with_allocations(mut self, allocations: Vec<MethodAllocation>) -> Self {
+        self.allocations = allocations;
+        self
+    }
+
+    pub fn with_payer(mut self, payer: CustomString) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    /// how the payment is shared, the payer bears the whole amount otherwise
+    pub fn with_share(mut self, share: Share) -> Self {
+        self.share = Some(share);
+        self
     }
 
     pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
@@ -165,14 +490,33 @@ impl PaymentDetail {
         if !valid_values.methods.contains(&self.method) {
             values.add_values(vec![], vec![], vec![self.method.clone()], vec![]);
         }
+        for allocation in &self.allocations {
+            if !valid_values.methods.contains(&allocation.method) {
+                values.add_values(vec![], vec![], vec![allocation.method.clone()], vec![]);
+            }
+        }
+        if let Some(share) = &self.share {
+            share.check_not_empty()?;
+        }
+        let participants = (self.payer.iter().cloned())
+            .chain(self.share.iter().flat_map(Share::participants))
+            .filter(|participant| !valid_values.participants.contains(participant));
+        values.add_participants(participants.collect::<Vec<_>>());
         values
             .is_empty()
             .then_some(())
             .ok_or(values)
+            .map_err(Box::new)
             .map_err(Error::MissingElements)
     }
 }
 
+impl MethodAllocation {
+    pub fn new(method: CustomString, amount: Money) -> Self {
+        Self { method, amount }
+    }
+}
+
 impl AllPayments {
     pub fn new() -> Self {
         Self::default()
@@ -186,17 +530,74 @@ impl AllPayments {
         AllPaymentsJson::from_api(self)?.dump_json(fmt)
     }
 
+    pub fn set_base_currency(&mut self, currency: Currency) {
+        self.base_currency = currency;
+    }
+
+    pub fn set_exchange_rates(&mut self, rates: ExchangeRates) {
+        self.exchange_rates = rates;
+    }
+
+    pub fn payment_currency(&self, paydetail: &PaymentDetail) -> Currency {
+        paydetail.currency.unwrap_or(self.base_currency)
+    }
+
+    /// converts an amount of the given payment into the base currency, using
+    /// the rate in effect on the payment date
+    pub fn to_base_currency(
+        &self,
+        payid: &PaymentId,
+        paydetail: &PaymentDetail,
+        amount: Money,
+    ) -> Result<Money> {
+        self.exchange_rates.convert(
+            amount,
+            self.payment_currency(paydetail),
+            self.base_currency,
+            payid.date,
+        )
+    }
+
+    pub fn calculate_total_price_base(&self, payid: &PaymentId) -> Result<Money> {
+        let payment = self
+            .payments
+            .get(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
+        let total = payment.calcualte_total_price()?;
+        self.to_base_currency(payid, &payment.payment_details, total)
+    }
+
     pub fn add_values(&mut self, new_values: ValueSet) {
         self.value_set.extend(new_values);
     }
 
+    /// first id at `date` not used by any payment yet
+    pub fn next_payment_id(&self, date: FakeUtcTime) -> Result<PaymentId> {
+        next_id(&self.payments, date)
+    }
+
     pub fn add_payment(&mut self, payid: PaymentId, paydetail: PaymentDetail) -> Result<()> {
         if self.payments.contains_key(&payid) {
             return Err(Error::PaymentDuplicated(payid));
         }
         paydetail.check_missing_elements(&self.value_set)?;
+        let payment = PayOrdersDetail::from(paydetail);
+        payment.check_allocations(&payid)?;
+
+        assert!(self.payments.insert(payid, payment).is_none());
+
+        Ok(())
+    }
+
+    /// adds a payment together with its orders
+    pub fn insert_payment(&mut self, payid: PaymentId, payment: PayOrdersDetail) -> Result<()> {
+        if self.payments.contains_key(&payid) {
+            return Err(Error::PaymentDuplicated(payid));
+        }
+        payment.check_missing_elements(&self.value_set)?;
+        payment.check_allocations(&payid)?;
 
-        assert!(self.payments.insert(payid, paydetail.into()).is_none());
+        assert!(self.payments.insert(payid, payment).is_none());
 
         Ok(())
     }
@@ -207,32 +608,39 @@ impl AllPayments {
         orderid: OrderId,
         orderdetail: OrderDetail,
     ) -> Result<()> {
-        let order_map = &mut self
+        let payment = self
             .payments
             .get_mut(payid)
-            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
-            .orders;
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
 
-        if order_map.contains_key(&orderid) {
+        if payment.orders.contains_key(&orderid) {
             return Err(Error::OrderDuplicated(payid.clone(), orderid.clone()));
         }
         orderid.check_missing_elements(&self.value_set)?;
+        orderdetail.check_missing_elements(&self.value_set)?;
 
-        assert!(order_map.insert(orderid, orderdetail).is_none());
-
-        Ok(())
+        assert!(
+            payment
+                .orders
+                .insert(orderid.clone(), orderdetail)
+                .is_none()
+        );
+        payment.check_allocations(payid).inspect_err(|_| {
+            payment.orders.remove(&orderid);
+        })
     }
 
     pub fn modify_payment(&mut self, payid: &PaymentId, paydetail: PaymentDetail) -> Result<()> {
-        let paydetail_mut = self
+        let payment = self
             .payments
             .get_mut(payid)
             .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
         paydetail.check_missing_elements(&self.value_set)?;
 
-        paydetail_mut.payment_details = paydetail;
-
-        Ok(())
+        let previous = std::mem::replace(&mut payment.payment_details, paydetail);
+        payment.check_allocations(payid).inspect_err(|_| {
+            payment.payment_details = previous;
+        })
     }
 
     pub fn modify_order(
@@ -241,19 +649,37 @@ impl AllPayments {
         orderid: &OrderId,
         orderdetail: OrderDetail,
     ) -> Result<()> {
-        let order_map = &mut self
+        let payment = self
             .payments
             .get_mut(payid)
-            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
-            .orders;
-        let orderdetail_mut = order_map
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
+        let orderdetail_mut = payment
+            .orders
             .get_mut(orderid)
             .ok_or_else(|| Error::OrderNotFound(payid.clone(), orderid.clone()))?;
         orderid.check_missing_elements(&self.value_set)?;
+        orderdetail.check_missing_elements(&self.value_set)?;
 
-        *orderdetail_mut = orderdetail;
+        let previous = std::mem::replace(orderdetail_mut, orderdetail);
+        payment.check_allocations(payid).inspect_err(|_| {
+            payment.orders.insert(orderid.clone(), previous);
+        })
+    }
 
-        Ok(())
+    pub fn modify_payment_discounts(
+        &mut self,
+        payid: &PaymentId,
+        discounts: Vec<Discount>,
+    ) -> Result<()> {
+        let payment = self
+            .payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
+
+        let previous = std::mem::replace(&mut payment.discounts, discounts);
+        payment.check_allocations(payid).inspect_err(|_| {
+            payment.discounts = previous;
+        })
     }
 
     pub fn remove_payment(&mut self, payid: &PaymentId) -> Result<()> {
@@ -264,22 +690,29 @@ impl AllPayments {
     }
 
     pub fn remove_order(&mut self, payid: &PaymentId, orderid: &OrderId) -> Result<()> {
-        let order_map = &mut self
+        let payment = self
             .payments
             .get_mut(payid)
-            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
-            .orders;
-        order_map
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?;
+        let previous = payment
+            .orders
             .remove(orderid)
-            .map(|_| ())
-            .ok_or_else(|| Error::OrderNotFound(payid.clone(), orderid.clone()))
+            .ok_or_else(|| Error::OrderNotFound(payid.clone(), orderid.clone()))?;
+        payment.check_allocations(payid).inspect_err(|_| {
+            payment.orders.insert(orderid.clone(), previous);
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        AllPayments, CustomString, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet,
+        AllPayments, CustomString, Money, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet,
+    };
+    use crate::{
+        error::Error,
+        rates::ExchangeRates,
+        types::{currency::Currency, discount::Discount},
     };
 
     #[test]
@@ -296,8 +729,8 @@ mod tests {
             CustomString::from("Cash"),
         );
         let orderid = OrderId::new(CustomString::from("Apple"));
-        let orderdetail = OrderDetail::new(120, 2);
-        let orderdetail2 = OrderDetail::new(120, 3);
+        let orderdetail = OrderDetail::new(Money::new(120), 2);
+        let orderdetail2 = OrderDetail::new(Money::new(120), 3);
         let mut values = ValueSet::new();
         values.add_values(
             vec![CustomString::from("London"), CustomString::from("Paris")],
@@ -348,6 +781,17 @@ mod tests {
         assert_eq!(newval, &orderdetail2);
         println!("MODIFIED ORDER: {all_payments:?}");
 
+        // total price
+        let pay1 = all_payments.payments().first_key_value().unwrap().1;
+        assert_eq!(pay1.calcualte_total_price().unwrap(), Money::new(360));
+        let overflow = OrderDetail::new(Money::new(i64::MAX), 2);
+        all_payments
+            .modify_order(&payid, &orderid, overflow)
+            .unwrap();
+        let pay1 = all_payments.payments().first_key_value().unwrap().1;
+        let res = pay1.calcualte_total_price();
+        assert!(matches!(res, Err(Error::MoneyOverflow)));
+
         // remove order
         let res = all_payments.remove_order(&payid, &orderid);
         assert!(res.is_ok());
@@ -361,4 +805,187 @@ mod tests {
         assert_eq!(all_payments.payments.len(), 0);
         println!("REMOVED PAYMENT: {all_payments:?}");
     }
+
+    #[test]
+    fn payments_same_minute() {
+        let date = 60.into();
+        let paydetail = PaymentDetail::new(
+            CustomString::from("London"),
+            CustomString::from("Pub"),
+            CustomString::from("Card"),
+        );
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("London")],
+            vec![CustomString::from("Pub")],
+            vec![CustomString::from("Card")],
+            vec![],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        let payid = all_payments.next_payment_id(date).unwrap();
+        assert_eq!(payid, PaymentId::new(date));
+        all_payments.add_payment(payid, paydetail.clone()).unwrap();
+
+        let payid2 = all_payments.next_payment_id(date).unwrap();
+        assert_eq!(payid2, PaymentId::new_seq(date, 1));
+        all_payments.add_payment(payid2, paydetail.clone()).unwrap();
+        assert!(
+            all_payments
+                .add_payment(PaymentId::new(date), paydetail.clone())
+                .is_err()
+        );
+
+        // ids at the same minute still sort between the surrounding minutes
+        let later = PaymentId::new(120.into());
+        all_payments
+            .add_payment(later.clone(), paydetail.clone())
+            .unwrap();
+        let range = all_payments.payments().range(PaymentId::new(date)..later);
+        assert_eq!(range.count(), 2);
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+
+        let last = PaymentId::new_seq(date, u32::MAX);
+        all_payments.add_payment(last, paydetail).unwrap();
+        assert!(all_payments.next_payment_id(date).is_err());
+    }
+
+    #[test]
+    fn payments_refund() {
+        let payid = PaymentId::new(0.into());
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Paris"),
+            CustomString::from("Market"),
+            CustomString::from("Cash"),
+        );
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Paris")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Bottle"), CustomString::from("Deposit")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        let bottle = OrderDetail::new(Money::new(150), 1);
+        let deposit = OrderDetail::new(Money::new(-25), 10);
+        all_payments
+            .add_order(&payid, CustomString::from("Bottle").into(), bottle)
+            .unwrap();
+        all_payments
+            .add_order(&payid, CustomString::from("Deposit").into(), deposit)
+            .unwrap();
+
+        let total = all_payments.calculate_total_price_base(&payid).unwrap();
+        assert_eq!(total, Money::new(-100));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+
+    #[test]
+    fn payments_discounts() {
+        let payid = PaymentId::new(0.into());
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Paris"),
+            CustomString::from("Market"),
+            CustomString::from("Cash"),
+        );
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Paris")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Apple"), CustomString::from("Banana")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        let apple =
+            OrderDetail::new(Money::new(333), 3).with_discounts(vec![Discount::Percent(1000)]);
+        let banana = OrderDetail::new(Money::new(100), 1);
+        let apple_id = OrderId::new(CustomString::from("Apple"));
+        let banana_id = OrderId::new(CustomString::from("Banana"));
+        all_payments
+            .add_order(&payid, apple_id.clone(), apple)
+            .unwrap();
+        all_payments
+            .add_order(&payid, banana_id.clone(), banana)
+            .unwrap();
+        let discounts = vec![Discount::Fixed(Money::new(100)), Discount::Percent(500)];
+        all_payments
+            .modify_payment_discounts(&payid, discounts)
+            .unwrap();
+
+        // apples 9.99 - 1.00 (rounded 10%), plus bananas 1.00, then - 1.00 - 0.45 (rounded 5%)
+        let payment = all_payments.payments().get(&payid).unwrap();
+        assert_eq!(payment.calculate_subtotal_price().unwrap(), Money::new(999));
+        assert_eq!(payment.calcualte_total_price().unwrap(), Money::new(854));
+        let prices = payment.calculate_order_prices().unwrap();
+        assert_eq!(prices[&banana_id], Money::new(85));
+        assert_eq!(prices[&apple_id], Money::new(769));
+        let list_price = payment.orders()[&apple_id].calculate_list_price().unwrap();
+        assert_eq!(list_price, Money::new(999));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+
+    #[test]
+    fn payments_currency() {
+        let usd = Currency::new("USD").unwrap();
+        let payid = PaymentId::new(0.into());
+        let paydetail = PaymentDetail::new(
+            CustomString::from("New York"),
+            CustomString::from("Diner"),
+            CustomString::from("Card"),
+        )
+        .with_currency(usd);
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("New York")],
+            vec![CustomString::from("Diner")],
+            vec![CustomString::from("Card")],
+            vec![CustomString::from("Pancake")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        let orderid = OrderId::new(CustomString::from("Pancake"));
+        let orderdetail = OrderDetail::new(Money::new(500), 2);
+        all_payments
+            .add_order(&payid, orderid, orderdetail)
+            .unwrap();
+
+        let res = all_payments.calculate_total_price_base(&payid);
+        assert!(matches!(res, Err(Error::ExchangeRateMissing(..))));
+
+        let mut rates = ExchangeRates::new();
+        rates
+            .add_rate(usd, Currency::default(), 0.into(), 500_000)
+            .unwrap();
+        assert!(
+            rates
+                .add_rate(usd, Currency::default(), 0.into(), 0)
+                .is_err()
+        );
+        all_payments.set_exchange_rates(rates);
+        let total = all_payments.calculate_total_price_base(&payid).unwrap();
+        assert_eq!(total, Money::new(500));
+
+        let json = all_payments.to_json(false).unwrap();
+        let mut all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments2, all_payments);
+        assert_eq!(all_payments2.base_currency(), &Currency::default());
+        all_payments2.set_base_currency(usd);
+        let total = all_payments2.calculate_total_price_base(&payid).unwrap();
+        assert_eq!(total, Money::new(1000));
+    }
 }
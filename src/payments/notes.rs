@@ -0,0 +1,54 @@
+use super::{AllPayments, OrderId, PaymentId};
+use crate::{
+    attachments::AttachmentHash,
+    error::{Error, Result},
+};
+
+impl AllPayments {
+    pub fn set_payment_note(&mut self, payid: &PaymentId, note: Option<String>) -> Result<()> {
+        self.payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
+            .note = note;
+        Ok(())
+    }
+
+    pub fn set_order_note(
+        &mut self,
+        payid: &PaymentId,
+        orderid: &OrderId,
+        note: Option<String>,
+    ) -> Result<()> {
+        self.payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
+            .orders
+            .get_mut(orderid)
+            .ok_or_else(|| Error::OrderNotFound(payid.clone(), orderid.clone()))?
+            .note = note;
+        Ok(())
+    }
+
+    /// references an attachment stored with `AttachmentStore::store`
+    pub fn attach(&mut self, payid: &PaymentId, hash: AttachmentHash) -> Result<()> {
+        self.payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
+            .attachments
+            .insert(hash);
+        Ok(())
+    }
+
+    /// drops the reference only, the stored file is left in place
+    pub fn detach(&mut self, payid: &PaymentId, hash: &AttachmentHash) -> Result<()> {
+        let removed = self
+            .payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
+            .attachments
+            .remove(hash);
+        removed
+            .then_some(())
+            .ok_or_else(|| Error::InvalidAttachment(hash.to_hex()))
+    }
+}
@@ -0,0 +1,276 @@
+use super::{AllPayments, PaymentId};
+use crate::{
+    error::{Error, Result},
+    time::FakeUtcTime,
+    types::{
+        internment::CustomString,
+        money::Money,
+        quantity::{MILLI_SCALE, Unit},
+    },
+};
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+
+/// (shop, city) a price series was bought at
+pub type ShopCity = (CustomString, CustomString);
+
+/// price paid per base unit (piece, kg or l) in one purchase, in the base
+/// currency, after discounts
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct PricePoint {
+    payid: PaymentId,
+    unit_price: Money,
+    /// thousandths of the base unit bought
+    quantity: u64,
+    /// price paid for the whole quantity
+    price: Money,
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PriceStats {
+    min: Money,
+    max: Money,
+    last: Money,
+    average: Money,
+}
+
+/// unit prices of an item bought in one base unit, keyed by (shop, city)
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct PriceHistory {
+    unit: Unit,
+    series: BTreeMap<ShopCity, Vec<PricePoint>>,
+    stats: PriceStats,
+}
+
+impl PriceStats {
+    /// `None` when there are no points; the average is weighted by the
+    /// quantity bought, so it is the total paid over the total quantity
+    fn from_points<'a, Iter: IntoIterator<Item = &'a PricePoint>>(
+        points: Iter,
+    ) -> Result<Option<Self>> {
+        let mut points: Vec<&PricePoint> = points.into_iter().collect();
+        points.sort_by(|a, b| a.payid.cmp(&b.payid));
+        let Some(last) = points.last() else {
+            return Ok(None);
+        };
+        let prices = points.iter().map(|point| point.unit_price);
+        let paid = Money::checked_sum(points.iter().map(|point| point.price))?;
+        let quantity = (points.iter())
+            .try_fold(0_u64, |acc, point| acc.checked_add(point.quantity))
+            .and_then(|quantity| i64::try_from(quantity).ok())
+            .ok_or(Error::MoneyOverflow)?;
+        Ok(Some(Self {
+            min: prices.clone().min().unwrap_or_default(),
+            max: prices.clone().max().unwrap_or_default(),
+            last: last.unit_price,
+            average: paid.checked_scale(MILLI_SCALE as i64, quantity)?,
+        }))
+    }
+}
+
+impl PriceHistory {
+    pub fn series_stats(
+        &self,
+        shop: &CustomString,
+        city: &CustomString,
+    ) -> Result<Option<PriceStats>> {
+        match self.series.get(&(shop.clone(), city.clone())) {
+            Some(points) => PriceStats::from_points(points),
+            None => Ok(None),
+        }
+    }
+}
+
+impl AllPayments {
+    fn item_price_points(
+        &self,
+        item: &CustomString,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<Vec<(PricePoint, Unit, ShopCity)>> {
+        let mut points = vec![];
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            for (orderid, price) in payment.calculate_order_prices()? {
+                if &orderid.item != item {
+                    continue;
+                }
+                let (base_milli, unit) = payment.orders[&orderid].quantity.to_base();
+                if base_milli == 0 {
+                    continue;
+                }
+                let milli = i64::try_from(base_milli).map_err(|_| Error::MoneyOverflow)?;
+                let price = self.to_base_currency(payid, &payment.payment_details, price)?;
+                let point = PricePoint {
+                    payid: payid.clone(),
+                    unit_price: price.checked_scale(MILLI_SCALE as i64, milli)?,
+                    quantity: base_milli,
+                    price,
+                };
+                let details = &payment.payment_details;
+                points.push((point, unit, (details.shop.clone(), details.city.clone())));
+            }
+        }
+        Ok(points)
+    }
+
+    /// unit prices of `item` bought from `start` (included) to `end`
+    /// (excluded), one history per base unit it was bought in, as prices
+    /// per piece and per kg do not compare; an end before the start selects
+    /// nothing
+    pub fn item_price_history(
+        &self,
+        item: &CustomString,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<Unit, PriceHistory>> {
+        let mut by_unit = BTreeMap::<Unit, BTreeMap<ShopCity, Vec<PricePoint>>>::new();
+        for (point, unit, key) in self.item_price_points(item, start, end)? {
+            let series = by_unit.entry(unit).or_default();
+            series.entry(key).or_default().push(point);
+        }
+
+        let mut histories = BTreeMap::new();
+        for (unit, series) in by_unit {
+            if let Some(stats) = PriceStats::from_points(series.values().flatten())? {
+                let history = PriceHistory {
+                    unit,
+                    series,
+                    stats,
+                };
+                histories.insert(unit, history);
+            }
+        }
+        Ok(histories)
+    }
+
+    /// shops sorted from the lowest average price of `item` per `unit`, which
+    /// must be a base unit, in the window, ties broken by name
+    pub fn cheapest_shops(
+        &self,
+        item: &CustomString,
+        unit: Unit,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<Vec<(CustomString, Money)>> {
+        let histories = self.item_price_history(item, start, end)?;
+        let Some(history) = histories.get(&unit) else {
+            return Ok(vec![]);
+        };
+        let mut shops = BTreeMap::<CustomString, Vec<&PricePoint>>::new();
+        for ((shop, _), points) in &history.series {
+            shops.entry(shop.clone()).or_default().extend(points);
+        }
+        let mut ranking = vec![];
+        for (shop, points) in shops {
+            if let Some(stats) = PriceStats::from_points(points)? {
+                ranking.push((shop, stats.average));
+            }
+        }
+        ranking.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(ranking)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        time::FakeUtcTime,
+        types::{
+            internment::CustomString,
+            money::Money,
+            quantity::{Quantity, Unit},
+        },
+    };
+
+    #[test]
+    fn price_history() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome"), CustomString::from("Milan")],
+            vec![CustomString::from("Market"), CustomString::from("Corner")],
+            vec![CustomString::from("Cash")],
+            vec![CustomString::from("Cheese")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+
+        let cheese = CustomString::from("Cheese");
+        let purchases = [
+            (
+                "2024/01/10 10:00",
+                "Rome",
+                "Market",
+                500,
+                Quantity::pieces(2),
+            ),
+            (
+                "2024/02/10 10:00",
+                "Rome",
+                "Market",
+                2,
+                Quantity::new(250_000, Unit::Grams),
+            ),
+            (
+                "2024/03/10 10:00",
+                "Milan",
+                "Market",
+                1800,
+                Quantity::new(500, Unit::Kilograms),
+            ),
+            (
+                "2024/04/10 10:00",
+                "Rome",
+                "Corner",
+                2100,
+                Quantity::new(1_000, Unit::Kilograms),
+            ),
+        ];
+        for (date, city, shop, price, quantity) in purchases {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new(city.into(), shop.into(), "Cash".into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            let orderdetail = OrderDetail::new(Money::new(price), quantity);
+            all_payments
+                .add_order(&payid, OrderId::new(cheese.clone()), orderdetail)
+                .unwrap();
+        }
+
+        let start = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let end = FakeUtcTime::parse_str("2025/01/01 00:00").unwrap();
+        let histories = all_payments
+            .item_price_history(&cheese, end, start)
+            .unwrap();
+        assert!(histories.is_empty());
+        let histories = all_payments
+            .item_price_history(&cheese, start, end)
+            .unwrap();
+        // the cheese bought by the piece gets its own history
+        assert_eq!(
+            histories.keys().collect::<Vec<_>>(),
+            vec![&Unit::Pieces, &Unit::Kilograms]
+        );
+        assert_eq!(histories[&Unit::Pieces].stats().average().cents(), 500);
+        let history = &histories[&Unit::Kilograms];
+        assert_eq!(history.series().len(), 3);
+        let stats = history.stats();
+        let cents = (
+            stats.min().cents(),
+            stats.max().cents(),
+            stats.last().cents(),
+            stats.average().cents(),
+        );
+        // 35.00 paid for 1.75 kg, not the plain average of 18.00, 20.00 and
+        // 21.00
+        assert_eq!(cents, (1800, 2100, 2100, 2000));
+
+        let ranking = all_payments
+            .cheapest_shops(&cheese, Unit::Kilograms, start, end)
+            .unwrap();
+        let ranking: Vec<&str> = ranking.iter().map(|(shop, _)| shop.as_str()).collect();
+        assert_eq!(ranking, vec!["Market", "Corner"]);
+    }
+}
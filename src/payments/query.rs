@@ -0,0 +1,316 @@
+use super::{AllPayments, OrderDetail, OrderId, PayOrdersDetail, PaymentId};
+use crate::{
+    error::Result,
+    time::FakeUtcTime,
+    types::{internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use std::{
+    collections::{BTreeSet, btree_map::Range},
+    ops::Bound,
+};
+
+/// filters over the payments of an `AllPayments`, every filter left unset
+/// matches everything; amounts are in the base currency
+#[derive(Debug, Clone)]
+pub struct Query<'a> {
+    all_payments: &'a AllPayments,
+    start: Option<FakeUtcTime>,
+    end: Option<FakeUtcTime>,
+    cities: BTreeSet<CustomString>,
+    shops: BTreeSet<CustomString>,
+    methods: BTreeSet<CustomString>,
+    items: BTreeSet<CustomString>,
+    min_total: Option<Money>,
+    max_total: Option<Money>,
+    min_line: Option<Money>,
+    max_line: Option<Money>,
+}
+
+/// an order line with its payment, `amount` is the line price after the
+/// payment discounts in the base currency
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct OrderLine<'a> {
+    payid: &'a PaymentId,
+    payment: &'a PayOrdersDetail,
+    orderid: &'a OrderId,
+    order: &'a OrderDetail,
+    amount: Money,
+}
+
+fn in_bounds(amount: Money, min: Option<Money>, max: Option<Money>) -> bool {
+    min.is_none_or(|min| amount >= min) && max.is_none_or(|max| amount <= max)
+}
+
+impl AllPayments {
+    pub fn query(&self) -> Query<'_> {
+        Query {
+            all_payments: self,
+            start: None,
+            end: None,
+            cities: BTreeSet::new(),
+            shops: BTreeSet::new(),
+            methods: BTreeSet::new(),
+            items: BTreeSet::new(),
+            min_total: None,
+            max_total: None,
+            min_line: None,
+            max_line: None,
+        }
+    }
+}
+
+impl<'a> Query<'a> {
+    /// payments from `start` (included) to `end` (excluded)
+    pub fn between(mut self, start: FakeUtcTime, end: FakeUtcTime) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+
+    pub fn from(mut self, start: FakeUtcTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn until(mut self, end: FakeUtcTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// can be repeated to match any of several cities, same for the other
+    /// values
+    pub fn city(mut self, city: CustomString) -> Self {
+        self.cities.insert(city);
+        self
+    }
+
+    pub fn shop(mut self, shop: CustomString) -> Self {
+        self.shops.insert(shop);
+        self
+    }
+
+    /// matches the payment method and the split allocations
+    pub fn method(mut self, method: CustomString) -> Self {
+        self.methods.insert(method);
+        self
+    }
+
+    /// keeps the lines of the item, and the payments with at least one
+    pub fn item(mut self, item: CustomString) -> Self {
+        self.items.insert(item);
+        self
+    }
+
+    pub fn min_total(mut self, amount: Money) -> Self {
+        self.min_total = Some(amount);
+        self
+    }
+
+    pub fn max_total(mut self, amount: Money) -> Self {
+        self.max_total = Some(amount);
+        self
+    }
+
+    pub fn min_line(mut self, amount: Money) -> Self {
+        self.min_line = Some(amount);
+        self
+    }
+
+    pub fn max_line(mut self, amount: Money) -> Self {
+        self.max_line = Some(amount);
+        self
+    }
+
+    fn has_line_filters(&self) -> bool {
+        !self.items.is_empty() || self.min_line.is_some() || self.max_line.is_some()
+    }
+
+    fn payment_matches(&self, payid: &PaymentId, payment: &PayOrdersDetail) -> Result<bool> {
+        let details = &payment.payment_details;
+        let matches_value =
+            |values: &BTreeSet<CustomString>, value| values.is_empty() || values.contains(value);
+        let methods_match = self.methods.is_empty()
+            || self.methods.contains(&details.method)
+            || (details.allocations.iter())
+                .any(|allocation| self.methods.contains(&allocation.method));
+        if !matches_value(&self.cities, &details.city)
+            || !matches_value(&self.shops, &details.shop)
+            || !methods_match
+        {
+            return Ok(false);
+        }
+        if self.min_total.is_none() && self.max_total.is_none() {
+            return Ok(true);
+        }
+        let total = self.all_payments.calculate_total_price_base(payid)?;
+        Ok(in_bounds(total, self.min_total, self.max_total))
+    }
+
+    fn payment_lines(
+        &self,
+        payid: &'a PaymentId,
+        payment: &'a PayOrdersDetail,
+    ) -> Result<Vec<OrderLine<'a>>> {
+        let mut lines = vec![];
+        for ((orderid, order), (_, price)) in
+            payment.orders.iter().zip(payment.calculate_order_prices()?)
+        {
+            if !self.items.is_empty() && !self.items.contains(&orderid.item) {
+                continue;
+            }
+            let amount =
+                (self.all_payments).to_base_currency(payid, &payment.payment_details, price)?;
+            if in_bounds(amount, self.min_line, self.max_line) {
+                lines.push(OrderLine {
+                    payid,
+                    payment,
+                    orderid,
+                    order,
+                    amount,
+                });
+            }
+        }
+        Ok(lines)
+    }
+
+    fn range(&self) -> Range<'a, PaymentId, PayOrdersDetail> {
+        // an end before the start matches nothing, `range` would panic
+        let end = (self.end).map(|end| self.start.map_or(end, |start| end.max(start)));
+        let start = self.start.map_or(Bound::Unbounded, |start| {
+            Bound::Included(PaymentId::new(start))
+        });
+        let end = end.map_or(Bound::Unbounded, |end| Bound::Excluded(PaymentId::new(end)));
+        self.all_payments.payments.range((start, end))
+    }
+
+    /// matching payments in date order
+    pub fn payments(
+        &self,
+    ) -> impl Iterator<Item = Result<(&'a PaymentId, &'a PayOrdersDetail)>> + '_ {
+        self.range().filter_map(move |(payid, payment)| {
+            let matches = self.payment_matches(payid, payment).and_then(|matches| {
+                Ok(matches
+                    && (!self.has_line_filters()
+                        || !self.payment_lines(payid, payment)?.is_empty()))
+            });
+            match matches {
+                Ok(true) => Some(Ok((payid, payment))),
+                Ok(false) => None,
+                Err(error) => Some(Err(error)),
+            }
+        })
+    }
+
+    /// matching order lines of the matching payments, in date order
+    pub fn lines(&self) -> impl Iterator<Item = Result<OrderLine<'a>>> + '_ {
+        self.range().flat_map(move |(payid, payment)| {
+            let lines = self
+                .payment_matches(payid, payment)
+                .and_then(|matches| match matches {
+                    true => self.payment_lines(payid, payment),
+                    false => Ok(vec![]),
+                });
+            match lines {
+                Ok(lines) => lines.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(error) => vec![Err(error)],
+            }
+        })
+    }
+
+    /// matching payments with their amount: the payment total converted to
+    /// the base currency as a whole, or the sum of the matching lines when
+    /// lines are filtered
+    pub fn amounts(
+        &self,
+    ) -> impl Iterator<Item = Result<(&'a PaymentId, &'a PayOrdersDetail, Money)>> + '_ {
+        self.payments().map(move |payment| {
+            let (payid, payment) = payment?;
+            let amount = match self.has_line_filters() {
+                true => (self.payment_lines(payid, payment)?.iter())
+                    .try_fold(Money::default(), |total, line| {
+                        total.checked_add(line.amount)
+                    })?,
+                false => self.all_payments.calculate_total_price_base(payid)?,
+            };
+            Ok((payid, payment, amount))
+        })
+    }
+
+    /// sum of the matching amounts
+    pub fn total(&self) -> Result<Money> {
+        self.amounts().try_fold(Money::default(), |total, amount| {
+            total.checked_add(amount?.2)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn query_payments_and_lines() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome"), CustomString::from("Milan")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Cash"), CustomString::from("Card")],
+            vec![CustomString::from("Bread"), CustomString::from("Wine")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let payments = [
+            (
+                "2024/01/10 10:00",
+                "Rome",
+                "Cash",
+                vec![("Bread", 300), ("Wine", 1200)],
+            ),
+            ("2024/01/20 10:00", "Milan", "Card", vec![("Bread", 250)]),
+            ("2024/02/10 10:00", "Rome", "Card", vec![("Wine", 900)]),
+        ];
+        for (date, city, method, orders) in payments {
+            let payid = PaymentId::new(FakeUtcTime::parse_str(date).unwrap());
+            let paydetail = PaymentDetail::new(city.into(), "Market".into(), method.into());
+            all_payments.add_payment(payid.clone(), paydetail).unwrap();
+            for (item, price) in orders {
+                let orderdetail = OrderDetail::new(Money::new(price), 1);
+                all_payments
+                    .add_order(&payid, OrderId::new(item.into()), orderdetail)
+                    .unwrap();
+            }
+        }
+
+        let start = FakeUtcTime::parse_str("2024/01/01 00:00").unwrap();
+        let end = FakeUtcTime::parse_str("2024/02/01 00:00").unwrap();
+        let january = all_payments.query().between(start, end);
+        assert_eq!(january.payments().count(), 2);
+        assert_eq!(january.total().unwrap().cents(), 1750);
+        assert_eq!(all_payments.query().between(end, start).lines().count(), 0);
+
+        let wine = all_payments.query().item("Wine".into());
+        assert_eq!(wine.payments().count(), 2);
+        assert_eq!(wine.total().unwrap().cents(), 2100);
+        let cheap = all_payments
+            .query()
+            .city("Rome".into())
+            .max_line(Money::new(1000));
+        let lines: Vec<(CustomString, i64)> = cheap
+            .lines()
+            .map(|line| line.map(|line| (line.orderid().item().clone(), line.amount().cents())))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected = vec![("Bread".into(), 300), ("Wine".into(), 900)];
+        assert_eq!(lines, expected);
+        let big = all_payments
+            .query()
+            .method("Card".into())
+            .min_total(Money::new(500));
+        assert_eq!(big.payments().count(), 1);
+    }
+}
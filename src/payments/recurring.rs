@@ -0,0 +1,341 @@
+use super::{AllPayments, PayOrdersDetail, PaymentId};
+use crate::{
+    error::{Error, Result},
+    time::{FakeUtcFields, FakeUtcTime},
+    types::internment::CustomString,
+};
+use chrono::{Datelike, Days, NaiveDate};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Recurrence {
+    /// on the given day of every month, or on its last day when shorter
+    MonthlyOnDay(u32),
+    Weekly,
+    /// on the start day of every year, 29 february falls back to the 28th
+    Yearly,
+    EveryDays(u32),
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct RecurringTemplate {
+    start: FakeUtcTime,
+    end: Option<FakeUtcTime>,
+    rule: Recurrence,
+    payment: PayOrdersDetail,
+    last_materialized: Option<FakeUtcTime>,
+}
+
+/// the template occurrence a payment was entered for, by hand or when
+/// materialized, so edits to the payment keep it linked
+#[derive(Getters, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct RecurringLink {
+    template: CustomString,
+    occurrence: FakeUtcTime,
+}
+
+fn clamped_date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+impl Recurrence {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Recurrence::MonthlyOnDay(day) => (1..=31).contains(day),
+            Recurrence::EveryDays(days) => *days > 0,
+            Recurrence::Weekly | Recurrence::Yearly => true,
+        }
+    }
+
+    /// the `index`-th candidate date, which may come before `start` for
+    /// monthly rules
+    fn nth(&self, start: FakeUtcFields, index: u32) -> Option<FakeUtcFields> {
+        let date = start.date_naive();
+        let date = match self {
+            Recurrence::EveryDays(days) => {
+                date.checked_add_days(Days::new(u64::from(*days).checked_mul(u64::from(index))?))
+            }
+            Recurrence::Weekly => {
+                date.checked_add_days(Days::new(7_u64.checked_mul(u64::from(index))?))
+            }
+            Recurrence::MonthlyOnDay(day) => {
+                let month0 = date.month0().checked_add(index)?;
+                let year = date.year().checked_add(i32::try_from(month0 / 12).ok()?)?;
+                clamped_date(year, month0 % 12 + 1, (*day).min(31))
+            }
+            Recurrence::Yearly => {
+                let year = date.year().checked_add(i32::try_from(index).ok()?)?;
+                clamped_date(year, date.month(), date.day())
+            }
+        }?;
+        Some(date.and_time(start.time()).and_utc())
+    }
+}
+
+impl RecurringLink {
+    pub fn new(template: CustomString, occurrence: FakeUtcTime) -> Self {
+        Self {
+            template,
+            occurrence,
+        }
+    }
+}
+
+impl RecurringTemplate {
+    pub fn new(start: FakeUtcTime, rule: Recurrence, payment: PayOrdersDetail) -> Self {
+        Self {
+            start,
+            end: None,
+            rule,
+            payment,
+            last_materialized: None,
+        }
+    }
+
+    /// no occurrence after `end`
+    pub fn with_end(mut self, end: FakeUtcTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// occurrences up to `last` are considered already entered
+    pub fn with_last_materialized(mut self, last: FakeUtcTime) -> Self {
+        self.last_materialized = Some(last);
+        self
+    }
+
+    pub(super) fn payment_mut(&mut self) -> &mut PayOrdersDetail {
+        &mut self.payment
+    }
+
+    /// every occurrence from the start up to `until`, both included
+    pub fn occurrences(&self, until: FakeUtcTime) -> Result<Vec<FakeUtcTime>> {
+        if !self.rule.is_valid() {
+            return Err(Error::InvalidRecurrence(self.rule));
+        }
+        let until = self.end.map_or(until, |end| end.min(until));
+        let start = self.start.get_fields()?;
+        let mut occurrences = vec![];
+        for index in 0.. {
+            let Some(occurrence) = self.rule.nth(start, index) else {
+                break;
+            };
+            let occurrence = FakeUtcTime::from_fields(occurrence);
+            if occurrence > until {
+                break;
+            }
+            if occurrence >= self.start {
+                occurrences.push(occurrence);
+            }
+        }
+        Ok(occurrences)
+    }
+}
+
+impl AllPayments {
+    pub fn add_recurring(&mut self, name: CustomString, template: RecurringTemplate) -> Result<()> {
+        if self.recurring.contains_key(&name) {
+            return Err(Error::RecurringDuplicated(name));
+        }
+        if !template.rule.is_valid() {
+            return Err(Error::InvalidRecurrence(template.rule));
+        }
+        template.payment.check_missing_elements(&self.value_set)?;
+
+        assert!(self.recurring.insert(name, template).is_none());
+
+        Ok(())
+    }
+
+    pub fn remove_recurring(&mut self, name: &CustomString) -> Result<()> {
+        self.recurring
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error::RecurringNotFound(name.clone()))
+    }
+
+    /// marks a payment entered by hand as the given occurrence of a template,
+    /// so that it is not materialized again; `None` unlinks it
+    pub fn link_recurring(&mut self, payid: &PaymentId, link: Option<RecurringLink>) -> Result<()> {
+        if let Some(link) = &link
+            && !self.recurring.contains_key(&link.template)
+        {
+            return Err(Error::RecurringNotFound(link.template.clone()));
+        }
+        self.payments
+            .get_mut(payid)
+            .ok_or_else(|| Error::PaymentNotFound(payid.clone()))?
+            .recurring = link;
+        Ok(())
+    }
+
+    /// occurrences already entered, by hand or materialized
+    pub(super) fn recurring_links(&self) -> BTreeSet<RecurringLink> {
+        (self.payments.values())
+            .filter_map(|payment| payment.recurring.clone())
+            .collect()
+    }
+
+    /// enters every occurrence due up to `until` that was not materialized
+    /// yet; an occurrence is skipped when a payment is already linked to it.
+    /// Nothing changes on error
+    pub fn materialize_recurring(&mut self, until: FakeUtcTime) -> Result<Vec<PaymentId>> {
+        let templates = self.recurring.clone();
+        let mut added = vec![];
+        let result = self.materialize_each(until, &mut added);
+        if result.is_err() {
+            for payid in &added {
+                self.payments.remove(payid);
+            }
+            self.recurring = templates;
+        }
+        result.map(|_| added)
+    }
+
+    fn materialize_each(&mut self, until: FakeUtcTime, added: &mut Vec<PaymentId>) -> Result<()> {
+        let mut links = self.recurring_links();
+        let names: Vec<CustomString> = self.recurring.keys().cloned().collect();
+        for name in names {
+            let template = self.recurring[&name].clone();
+            for date in template.occurrences(until)? {
+                if template.last_materialized.is_some_and(|last| date <= last) {
+                    continue;
+                }
+                let link = RecurringLink::new(name.clone(), date);
+                if links.insert(link.clone()) {
+                    let payid = self.next_payment_id(date)?;
+                    let payment = template.payment.clone().with_recurring(link);
+                    self.insert_payment(payid.clone(), payment)?;
+                    added.push(payid);
+                }
+                if let Some(template) = self.recurring.get_mut(&name) {
+                    template.last_materialized = Some(date);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recurrence, RecurringLink, RecurringTemplate};
+    use crate::{
+        payments::{
+            AllPayments, OrderDetail, OrderId, PayOrdersDetail, PaymentDetail, PaymentId, ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    fn occurrences(start: &str, rule: Recurrence, until: &str) -> Vec<String> {
+        let payment = PaymentDetail::new("A".into(), "B".into(), "C".into()).into();
+        let start = FakeUtcTime::parse_str(start).unwrap();
+        let template = RecurringTemplate::new(start, rule, payment);
+        let until = FakeUtcTime::parse_str(until).unwrap();
+        let occurrences = template.occurrences(until).unwrap();
+        occurrences
+            .iter()
+            .map(|time| time.format_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn recurrence_rules() {
+        let monthly = occurrences(
+            "2024/01/15 10:00",
+            Recurrence::MonthlyOnDay(31),
+            "2024/04/30 10:00",
+        );
+        assert_eq!(
+            monthly,
+            vec![
+                "2024/01/31 10:00",
+                "2024/02/29 10:00",
+                "2024/03/31 10:00",
+                "2024/04/30 10:00"
+            ]
+        );
+        let monthly = occurrences(
+            "2024/01/15 10:00",
+            Recurrence::MonthlyOnDay(5),
+            "2024/03/01 10:00",
+        );
+        assert_eq!(monthly, vec!["2024/02/05 10:00"]);
+        let weekly = occurrences("2024/01/01 08:30", Recurrence::Weekly, "2024/01/15 08:29");
+        assert_eq!(weekly, vec!["2024/01/01 08:30", "2024/01/08 08:30"]);
+        let yearly = occurrences("2024/02/29 00:00", Recurrence::Yearly, "2026/01/01 00:00");
+        assert_eq!(yearly, vec!["2024/02/29 00:00", "2025/02/28 00:00"]);
+        let every = occurrences(
+            "2024/01/30 00:00",
+            Recurrence::EveryDays(3),
+            "2024/02/05 00:00",
+        );
+        assert_eq!(
+            every,
+            vec!["2024/01/30 00:00", "2024/02/02 00:00", "2024/02/05 00:00"]
+        );
+    }
+
+    #[test]
+    fn long_steps_do_not_overflow() {
+        let start = FakeUtcTime::parse_str("2024/01/30 00:00").unwrap();
+        let fields = start.get_fields().unwrap();
+        assert_eq!(Recurrence::EveryDays(u32::MAX).nth(fields, u32::MAX), None);
+        assert_eq!(Recurrence::MonthlyOnDay(1).nth(fields, u32::MAX), None);
+    }
+
+    #[test]
+    fn materialize_recurring() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Milan")],
+            vec![CustomString::from("Landlord")],
+            vec![CustomString::from("Bank")],
+            vec![CustomString::from("Rent")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let paydetail = PaymentDetail::new("Milan".into(), "Landlord".into(), "Bank".into());
+        let rent = OrderDetail::new(Money::new(80_000), 1);
+        let payment = PayOrdersDetail::new(paydetail).with_order(OrderId::new("Rent".into()), rent);
+        let start = FakeUtcTime::parse_str("2024/01/01 09:00").unwrap();
+        let template = RecurringTemplate::new(start, Recurrence::MonthlyOnDay(1), payment.clone());
+        all_payments
+            .add_recurring("Rent".into(), template.clone())
+            .unwrap();
+        assert!(all_payments.add_recurring("Rent".into(), template).is_err());
+
+        // february was already entered by hand, a day late and with a note
+        let february = FakeUtcTime::parse_str("2024/02/01 09:00").unwrap();
+        let entered = PaymentId::new(february.add_days(1).unwrap());
+        let edited = payment.with_note("paid late".into());
+        all_payments
+            .insert_payment(entered.clone(), edited)
+            .unwrap();
+        let link = RecurringLink::new("Rent".into(), february);
+        all_payments.link_recurring(&entered, Some(link)).unwrap();
+
+        let until = FakeUtcTime::parse_str("2024/03/15 00:00").unwrap();
+        let added = all_payments.materialize_recurring(until).unwrap();
+        assert_eq!(added.len(), 2);
+        assert_eq!(all_payments.payments().len(), 3);
+        assert!(
+            all_payments
+                .materialize_recurring(until)
+                .unwrap()
+                .is_empty()
+        );
+
+        let json = all_payments.to_json(false).unwrap();
+        let mut all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+        let until = FakeUtcTime::parse_str("2024/04/01 09:00").unwrap();
+        assert_eq!(all_payments2.materialize_recurring(until).unwrap().len(), 1);
+    }
+}
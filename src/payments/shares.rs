@@ -0,0 +1,425 @@
+use super::{AllPayments, PaymentId, ValueSet};
+use crate::{
+    error::{Error, Result},
+    time::FakeUtcTime,
+    types::{internment::CustomString, money::Money},
+};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// how an amount is shared between participants
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Share {
+    Equal(Vec<CustomString>),
+    Shares(BTreeMap<CustomString, u32>),
+    Exact(BTreeMap<CustomString, Money>),
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone)]
+pub struct Transfer {
+    from: CustomString,
+    to: CustomString,
+    amount: Money,
+}
+
+/// above this many open balances the exact search gets too slow, and the
+/// balances are settled as a single group instead
+const MAX_EXACT_SETTLE: usize = 16;
+
+/// splits the balances into the largest number of groups that sum to zero,
+/// searching every subset
+fn zero_sum_groups(
+    balances: Vec<(CustomString, Money)>,
+) -> Result<Vec<Vec<(CustomString, Money)>>> {
+    let len = balances.len();
+    if len > MAX_EXACT_SETTLE {
+        return Ok(vec![balances]);
+    }
+    let full = (1_usize << len) - 1;
+    let mut sums = vec![Money::default(); full + 1];
+    let mut groups = vec![0_u32; full + 1];
+    for mask in 1..=full {
+        let lowest = mask.trailing_zeros() as usize;
+        sums[mask] = sums[mask & (mask - 1)].checked_add(balances[lowest].1)?;
+        let best = (0..len)
+            .filter(|index| mask & (1 << index) != 0)
+            .map(|index| groups[mask ^ (1 << index)])
+            .max()
+            .unwrap_or(0);
+        groups[mask] = best + u32::from(sums[mask] == Money::default());
+    }
+
+    // remove one balance at a time along the best path, a group closes every
+    // time the balances left sum to zero again
+    let mut result = vec![];
+    let mut current = vec![];
+    let mut mask = full;
+    while mask != 0 {
+        let closes = u32::from(sums[mask] == Money::default());
+        if closes == 1 && !current.is_empty() {
+            result.push(std::mem::take(&mut current));
+        }
+        let Some(index) = (0..len).find(|index| {
+            mask & (1 << index) != 0 && groups[mask ^ (1 << index)] + closes == groups[mask]
+        }) else {
+            break;
+        };
+        current.push(balances[index].clone());
+        mask ^= 1 << index;
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    Ok(result)
+}
+
+/// the fewest transfers that settle `balances`: a group of n balances summing
+/// to zero needs n - 1 transfers, so the balances are split into as many such
+/// groups as possible, then every group matches its largest debtor with its
+/// largest creditor until it is settled
+fn settle(balances: Vec<(CustomString, Money)>) -> Result<Vec<Transfer>> {
+    let open = balances
+        .into_iter()
+        .filter(|(_, balance)| *balance != Money::default());
+    let mut transfers = vec![];
+    for group in zero_sum_groups(open.collect())? {
+        let mut debtors: Vec<(CustomString, Money)> = vec![];
+        let mut creditors: Vec<(CustomString, Money)> = vec![];
+        for (participant, balance) in group {
+            if balance.is_negative() {
+                debtors.push((participant, balance.checked_abs()?));
+            } else {
+                creditors.push((participant, balance));
+            }
+        }
+        loop {
+            debtors.sort_by_key(|(_, amount)| *amount);
+            creditors.sort_by_key(|(_, amount)| *amount);
+            let (Some(debtor), Some(creditor)) = (debtors.pop(), creditors.pop()) else {
+                break;
+            };
+            let amount = debtor.1.min(creditor.1);
+            transfers.push(Transfer {
+                from: debtor.0.clone(),
+                to: creditor.0.clone(),
+                amount,
+            });
+            if debtor.1 > amount {
+                debtors.push((debtor.0, debtor.1.checked_sub(amount)?));
+            }
+            if creditor.1 > amount {
+                creditors.push((creditor.0, creditor.1.checked_sub(amount)?));
+            }
+        }
+    }
+    Ok(transfers)
+}
+
+fn split_weighted(
+    weights: Vec<(CustomString, u32)>,
+    amount: Money,
+) -> Result<Vec<(CustomString, Money)>> {
+    let total_weight: i64 = weights.iter().map(|(_, w)| i64::from(*w)).sum();
+    if total_weight == 0 {
+        return Err(Error::EmptyShare);
+    }
+    let mut parts = vec![];
+    let mut allocated = Money::default();
+    for (participant, weight) in &weights {
+        // never larger than amount, so the cast back is lossless
+        let part = i128::from(amount.cents()) * i128::from(*weight) / i128::from(total_weight);
+        let part = Money::new(part as i64);
+        allocated = allocated.checked_add(part)?;
+        parts.push((participant.clone(), part));
+    }
+
+    // hand out the truncated cents one by one, largest weights first
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(weights[index].1));
+    let step = Money::new(amount.cents().signum());
+    let mut leftover = amount.checked_sub(allocated)?;
+    for index in order.into_iter().cycle() {
+        if leftover == Money::default() {
+            break;
+        }
+        parts[index].1 = parts[index].1.checked_add(step)?;
+        leftover = leftover.checked_sub(step)?;
+    }
+    Ok(parts)
+}
+
+impl Share {
+    pub fn participants(&self) -> Vec<CustomString> {
+        match self {
+            Share::Equal(participants) => participants.clone(),
+            Share::Shares(shares) => shares.keys().cloned().collect(),
+            Share::Exact(amounts) => amounts.keys().cloned().collect(),
+        }
+    }
+
+    /// a share needs at least one participant with a non-zero weight
+    pub fn check_not_empty(&self) -> Result<()> {
+        let empty = match self {
+            Share::Equal(participants) => participants.is_empty(),
+            Share::Shares(shares) => shares.values().all(|weight| *weight == 0),
+            Share::Exact(amounts) => amounts.is_empty(),
+        };
+        (!empty).then_some(()).ok_or(Error::EmptyShare)
+    }
+
+    pub fn check_missing_elements(&self, valid_values: &ValueSet) -> Result<()> {
+        self.check_not_empty()?;
+        let mut values = ValueSet::new();
+        let missing = self
+            .participants()
+            .into_iter()
+            .filter(|participant| !valid_values.participants.contains(participant));
+        values.add_participants(missing);
+        values
+            .is_empty()
+            .then_some(())
+            .ok_or(values)
+            .map_err(Box::new)
+            .map_err(Error::MissingElements)
+    }
+
+    /// splits `amount` between the participants; leftover cents of equal
+    /// splits go to the first participants, those of weighted splits to the
+    /// largest share, so the parts always add up to `amount`
+    pub fn split(&self, payid: &PaymentId, amount: Money) -> Result<Vec<(CustomString, Money)>> {
+        let mut parts = vec![];
+        match self {
+            Share::Equal(participants) => {
+                let weights = participants.iter().map(|p| (p.clone(), 1));
+                return split_weighted(weights.collect(), amount);
+            }
+            Share::Shares(shares) => {
+                let weights = shares.iter().map(|(p, w)| (p.clone(), *w));
+                return split_weighted(weights.collect(), amount);
+            }
+            Share::Exact(amounts) => {
+                let allocated = Money::checked_sum(amounts.values().copied())?;
+                if allocated != amount {
+                    return Err(Error::AllocationMismatch(payid.clone(), amount, allocated));
+                }
+                parts.extend(amounts.iter().map(|(p, a)| (p.clone(), *a)));
+            }
+        }
+        Ok(parts)
+    }
+}
+
+impl AllPayments {
+    /// what every participant is owed (positive) or owes (negative) for the
+    /// payments from `start` (included) to `end` (excluded) in the base
+    /// currency, an end before the start selects nothing; a line is paid by
+    /// its own payer, or else by the payment payer, and lines without either
+    /// are left out
+    pub fn balances(
+        &self,
+        start: FakeUtcTime,
+        end: FakeUtcTime,
+    ) -> Result<BTreeMap<CustomString, Money>> {
+        let mut balances: BTreeMap<CustomString, Money> = BTreeMap::new();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            let details = &payment.payment_details;
+
+            // lines with their own share are split alone, the others together
+            // with the rest paid by the same payer
+            let mut parts = vec![];
+            let mut rest: BTreeMap<&CustomString, Money> = BTreeMap::new();
+            for (orderid, price) in payment.calculate_order_prices()? {
+                let order = &payment.orders[&orderid];
+                let Some(payer) = order.payer.as_ref().or(details.payer.as_ref()) else {
+                    continue;
+                };
+                match &order.share {
+                    Some(share) => parts.push((payer, share.split(payid, price)?)),
+                    None => {
+                        let amount = rest.entry(payer).or_default();
+                        *amount = amount.checked_add(price)?;
+                    }
+                }
+            }
+            for (payer, amount) in rest {
+                match &details.share {
+                    Some(share) => parts.push((payer, share.split(payid, amount)?)),
+                    None => parts.push((payer, vec![(payer.clone(), amount)])),
+                }
+            }
+
+            for (payer, split) in parts {
+                for (participant, part) in split {
+                    let part = self.to_base_currency(payid, details, part)?;
+                    let debt = balances.entry(participant).or_default();
+                    *debt = debt.checked_sub(part)?;
+                    let credit = balances.entry(payer.clone()).or_default();
+                    *credit = credit.checked_add(part)?;
+                }
+            }
+        }
+        Ok(balances)
+    }
+
+    /// the fewest transfers that settle every balance of the period, see
+    /// `settle`
+    pub fn settle_up(&self, start: FakeUtcTime, end: FakeUtcTime) -> Result<Vec<Transfer>> {
+        settle(self.balances(start, end)?.into_iter().collect())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::{Share, settle};
+    use crate::{
+        error::Error,
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        types::{internment::CustomString, money::Money},
+    };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn share_split() {
+        let payid = PaymentId::new(0.into());
+        let (a, b, c) = ("A".into(), "B".into(), "C".into());
+        let equal = Share::Equal(vec![a, b, c]);
+        let parts = equal.split(&payid, Money::new(100)).unwrap();
+        let parts: Vec<i64> = parts.iter().map(|(_, part)| part.cents()).collect();
+        assert_eq!(parts, vec![34, 33, 33]);
+
+        let shares = Share::Shares(BTreeMap::from([("A".into(), 1), ("B".into(), 2)]));
+        let parts = shares.split(&payid, Money::new(-100)).unwrap();
+        let parts: Vec<i64> = parts.iter().map(|(_, part)| part.cents()).collect();
+        assert_eq!(parts, vec![-33, -67]);
+
+        let exact = Share::Exact(BTreeMap::from([("A".into(), Money::new(60))]));
+        assert!(exact.split(&payid, Money::new(100)).is_err());
+        assert!(matches!(
+            Share::Equal(vec![]).check_not_empty(),
+            Err(Error::EmptyShare)
+        ));
+    }
+
+    #[test]
+    fn minimal_settle() {
+        // matching the largest balances first would need five transfers
+        let balances = [
+            ("A", 600),
+            ("B", 400),
+            ("C", -300),
+            ("D", -300),
+            ("E", -200),
+        ];
+        let mut balances: Vec<(CustomString, Money)> = (balances.into_iter())
+            .map(|(name, balance)| (name.into(), Money::new(balance)))
+            .collect();
+        balances.push(("F".into(), Money::new(-200)));
+        let transfers = settle(balances).unwrap();
+        assert_eq!(transfers.len(), 4);
+        let to_a = transfers
+            .iter()
+            .filter(|transfer| transfer.to().as_str() == "A");
+        assert_eq!(
+            to_a.map(|transfer| transfer.amount().cents()).sum::<i64>(),
+            600
+        );
+    }
+
+    #[test]
+    fn settle_up() {
+        let (anna, bob, carl) = (
+            CustomString::from("Anna"),
+            CustomString::from("Bob"),
+            CustomString::from("Carl"),
+        );
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Turin")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Card")],
+            vec![CustomString::from("Pasta"), CustomString::from("Wine")],
+        );
+        values.add_participants(vec![anna.clone(), bob.clone(), carl.clone()]);
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Turin"),
+            CustomString::from("Market"),
+            CustomString::from("Card"),
+        );
+
+        // anna buys pasta for everyone, and wine shared by bob and carl only
+        let payid = PaymentId::new(0.into());
+        let share = Share::Equal(vec![anna.clone(), bob.clone(), carl.clone()]);
+        let anna_pays = paydetail.clone().with_payer(anna.clone()).with_share(share);
+        all_payments.add_payment(payid.clone(), anna_pays).unwrap();
+        let pasta = OrderDetail::new(Money::new(300), 3);
+        let wine_share = Share::Shares(BTreeMap::from([(bob.clone(), 1), (carl.clone(), 1)]));
+        let wine = OrderDetail::new(Money::new(1000), 1).with_share(wine_share);
+        all_payments
+            .add_order(&payid, OrderId::new("Pasta".into()), pasta)
+            .unwrap();
+        all_payments
+            .add_order(&payid, OrderId::new("Wine".into()), wine)
+            .unwrap();
+
+        // bob pays 6.00 with 4.00 owed by carl
+        let payid2 = PaymentId::new(60.into());
+        let share = Share::Exact(BTreeMap::from([
+            (bob.clone(), Money::new(200)),
+            (carl.clone(), Money::new(400)),
+        ]));
+        let bob_pays = paydetail.clone().with_payer(bob.clone()).with_share(share);
+        all_payments.add_payment(payid2.clone(), bob_pays).unwrap();
+        let pasta = OrderDetail::new(Money::new(300), 2);
+        all_payments
+            .add_order(&payid2, OrderId::new("Pasta".into()), pasta)
+            .unwrap();
+
+        let balances = all_payments.balances(0.into(), 120.into()).unwrap();
+        assert_eq!(balances[&anna], Money::new(1600));
+        assert_eq!(balances[&bob], Money::new(-400));
+        assert_eq!(balances[&carl], Money::new(-1200));
+
+        let transfers = all_payments.settle_up(0.into(), 120.into()).unwrap();
+        assert!(
+            all_payments
+                .settle_up(120.into(), 0.into())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].from(), &carl);
+        assert_eq!(transfers[0].amount(), &Money::new(1200));
+        assert_eq!(transfers[1].from(), &bob);
+        assert_eq!(transfers[1].to(), &anna);
+
+        // carl pays for wine shared with anna, in a payment nobody else paid
+        let payid3 = PaymentId::new(120.into());
+        all_payments
+            .add_payment(payid3.clone(), paydetail.clone())
+            .unwrap();
+        let wine_share = Share::Equal(vec![anna.clone(), carl.clone()]);
+        let wine =
+            (OrderDetail::new(Money::new(1000), 1).with_share(wine_share)).with_payer(carl.clone());
+        all_payments
+            .add_order(&payid3, OrderId::new("Wine".into()), wine)
+            .unwrap();
+        let balances = all_payments.balances(0.into(), 180.into()).unwrap();
+        assert_eq!(balances[&anna], Money::new(1100));
+        assert_eq!(balances[&carl], Money::new(-700));
+        let empty_share = paydetail.with_share(Share::Equal(vec![]));
+        assert!(matches!(
+            all_payments.add_payment(PaymentId::new(180.into()), empty_share),
+            Err(Error::EmptyShare)
+        ));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
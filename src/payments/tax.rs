@@ -0,0 +1,106 @@
+use super::{AllPayments, PaymentId};
+use crate::{error::Result, time::FakeUtcTime, types::tax::TaxTotals};
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+
+/// net, tax and gross totals in the base currency; rates are hundredths of a
+/// percent, with `None` collecting the untaxed orders
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+pub struct TaxReport {
+    payments: BTreeMap<PaymentId, TaxTotals>,
+    rates: BTreeMap<Option<u32>, TaxTotals>,
+}
+
+impl AllPayments {
+    /// tax breakdown of the payments from `start` (included) to `end`
+    /// (excluded), an end before the start selects nothing; payment
+    /// discounts reduce every rate proportionally
+    pub fn tax_report(&self, start: FakeUtcTime, end: FakeUtcTime) -> Result<TaxReport> {
+        let mut report = TaxReport::default();
+        let range = self
+            .payments
+            .range(PaymentId::new(start)..PaymentId::new(end.max(start)));
+        for (payid, payment) in range {
+            let mut payment_totals = TaxTotals::default();
+            for (orderid, price) in payment.calculate_order_prices()? {
+                let tax = payment.orders[&orderid].tax;
+                let gross = self.to_base_currency(payid, &payment.payment_details, price)?;
+                let totals = match tax {
+                    Some(tax) => tax.split(gross)?,
+                    None => TaxTotals::untaxed(gross),
+                };
+                payment_totals = payment_totals.checked_add(totals)?;
+                let rate_totals = report.rates.entry(tax.map(|tax| *tax.rate())).or_default();
+                *rate_totals = rate_totals.checked_add(totals)?;
+            }
+            report.payments.insert(payid.clone(), payment_totals);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        payments::{AllPayments, OrderDetail, OrderId, PaymentDetail, PaymentId, ValueSet},
+        types::{
+            internment::CustomString,
+            money::Money,
+            tax::{Tax, TaxMode},
+        },
+    };
+
+    #[test]
+    fn tax_report() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Rome")],
+            vec![CustomString::from("Market")],
+            vec![CustomString::from("Card")],
+            vec![CustomString::from("Bread"), CustomString::from("Soap")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let paydetail = PaymentDetail::new(
+            CustomString::from("Rome"),
+            CustomString::from("Market"),
+            CustomString::from("Card"),
+        );
+        let bread = OrderId::new(CustomString::from("Bread"));
+        let soap = OrderId::new(CustomString::from("Soap"));
+        for time in [0, 60, 3600] {
+            let payid = PaymentId::new(time.into());
+            all_payments
+                .add_payment(payid.clone(), paydetail.clone())
+                .unwrap();
+            let bread_tax = Tax::new(400, TaxMode::Inclusive);
+            let bread_detail = OrderDetail::new(Money::new(104), 1).with_tax(bread_tax);
+            let soap_tax = Tax::new(2200, TaxMode::Exclusive);
+            let soap_detail = OrderDetail::new(Money::new(100), 2).with_tax(soap_tax);
+            all_payments
+                .add_order(&payid, bread.clone(), bread_detail)
+                .unwrap();
+            all_payments
+                .add_order(&payid, soap.clone(), soap_detail)
+                .unwrap();
+        }
+
+        let report = all_payments.tax_report(3600.into(), 0.into()).unwrap();
+        assert!(report.payments().is_empty());
+        let report = all_payments.tax_report(0.into(), 3600.into()).unwrap();
+        assert_eq!(report.payments().len(), 2);
+        let payment = report.payments()[&PaymentId::new(0.into())];
+        assert_eq!(payment.net(), &Money::new(300));
+        assert_eq!(payment.tax(), &Money::new(48));
+        assert_eq!(payment.gross(), &Money::new(348));
+        let bread_rate = report.rates()[&Some(400)];
+        assert_eq!(bread_rate.net(), &Money::new(200));
+        assert_eq!(bread_rate.tax(), &Money::new(8));
+        let soap_rate = report.rates()[&Some(2200)];
+        assert_eq!(soap_rate.gross(), &Money::new(488));
+
+        let json = all_payments.to_json(false).unwrap();
+        let all_payments2 = AllPayments::from_json(&json).unwrap();
+        assert_eq!(all_payments, all_payments2);
+    }
+}
@@ -0,0 +1,644 @@
+use super::{
+    AllPayments, BudgetScope, IncomeId, MethodAllocation, OrderDetail, OrderId, PayOrdersDetail,
+    PaymentDetail, PaymentId, ValueSet,
+};
+use crate::{
+    error::{Error, Result},
+    types::{internment::CustomString, money::Money, quantity::Quantity},
+};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+/// the value set entries that payments refer to by name
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValueKind {
+    City,
+    Shop,
+    Method,
+    Item,
+}
+
+/// everything that still refers to a value, which can't be removed until
+/// this is empty
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ValueReferences {
+    payments: Vec<PaymentId>,
+    recurring: Vec<CustomString>,
+    budgets: Vec<CustomString>,
+    incomes: Vec<IncomeId>,
+    transfers: Vec<PaymentId>,
+    account: bool,
+}
+
+impl ValueReferences {
+    pub fn is_empty(&self) -> bool {
+        self.payments.is_empty()
+            && self.recurring.is_empty()
+            && self.budgets.is_empty()
+            && self.incomes.is_empty()
+            && self.transfers.is_empty()
+            && !self.account
+    }
+}
+
+impl ValueKind {
+    pub const ALL: [ValueKind; 4] = [
+        ValueKind::City,
+        ValueKind::Shop,
+        ValueKind::Method,
+        ValueKind::Item,
+    ];
+}
+
+impl Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ValueKind::City => "city",
+            ValueKind::Shop => "shop",
+            ValueKind::Method => "payment method",
+            ValueKind::Item => "item",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl ValueSet {
+    pub fn values(&self, kind: ValueKind) -> &BTreeSet<CustomString> {
+        match kind {
+            ValueKind::City => &self.cities,
+            ValueKind::Shop => &self.shops,
+            ValueKind::Method => &self.methods,
+            ValueKind::Item => &self.items,
+        }
+    }
+
+    pub(super) fn values_mut(&mut self, kind: ValueKind) -> &mut BTreeSet<CustomString> {
+        match kind {
+            ValueKind::City => &mut self.cities,
+            ValueKind::Shop => &mut self.shops,
+            ValueKind::Method => &mut self.methods,
+            ValueKind::Item => &mut self.items,
+        }
+    }
+
+    fn sets(&self) -> [&BTreeSet<CustomString>; 7] {
+        [
+            &self.cities,
+            &self.shops,
+            &self.methods,
+            &self.items,
+            &self.participants,
+            &self.categories,
+            &self.sources,
+        ]
+    }
+
+    fn sets_mut(&mut self) -> [&mut BTreeSet<CustomString>; 7] {
+        [
+            &mut self.cities,
+            &mut self.shops,
+            &mut self.methods,
+            &mut self.items,
+            &mut self.participants,
+            &mut self.categories,
+            &mut self.sources,
+        ]
+    }
+
+    /// the values of `self` that are not in `other`
+    pub fn difference(&self, other: &ValueSet) -> ValueSet {
+        let mut difference = ValueSet::new();
+        let sets = self.sets().into_iter().zip(other.sets());
+        for ((set, other_set), result) in sets.zip(difference.sets_mut()) {
+            *result = set.difference(other_set).cloned().collect();
+        }
+        difference
+    }
+
+    /// removes every value of `other`, without checking if it is in use
+    pub(super) fn remove_all(&mut self, other: &ValueSet) {
+        for (set, other_set) in self.sets_mut().into_iter().zip(other.sets()) {
+            set.retain(|value| !other_set.contains(value));
+        }
+    }
+
+    pub(super) fn single(kind: ValueKind, value: &CustomString) -> ValueSet {
+        let mut values = ValueSet::new();
+        values.values_mut(kind).insert(value.clone());
+        values
+    }
+
+    fn missing(kind: ValueKind, value: &CustomString) -> Error {
+        Error::MissingElements(Box::new(ValueSet::single(kind, value)))
+    }
+}
+
+fn replace(value: &mut CustomString, from: &CustomString, to: &CustomString) {
+    if value == from {
+        *value = to.clone();
+    }
+}
+
+impl PaymentDetail {
+    fn replace_value(
+        &mut self,
+        kind: ValueKind,
+        from: &CustomString,
+        to: &CustomString,
+    ) -> Result<()> {
+        match kind {
+            ValueKind::City => replace(&mut self.city, from, to),
+            ValueKind::Shop => replace(&mut self.shop, from, to),
+            ValueKind::Method => {
+                replace(&mut self.method, from, to);
+                // allocations that end up on the same method are summed
+                let mut allocations: Vec<MethodAllocation> = vec![];
+                for mut allocation in self.allocations.drain(..) {
+                    replace(&mut allocation.method, from, to);
+                    match allocations
+                        .iter_mut()
+                        .find(|a| a.method == allocation.method)
+                    {
+                        Some(existing) => {
+                            existing.amount = existing.amount.checked_add(allocation.amount)?;
+                        }
+                        None => allocations.push(allocation),
+                    }
+                }
+                self.allocations = allocations;
+            }
+            ValueKind::Item => {}
+        }
+        Ok(())
+    }
+}
+
+impl OrderDetail {
+    /// the two lines as a single one with the summed quantity, when they only
+    /// differ by quantity
+    fn combine(&self, other: &OrderDetail) -> Option<OrderDetail> {
+        let unit = self.quantity.unit();
+        let same_line = OrderDetail {
+            quantity: other.quantity,
+            ..self.clone()
+        } == *other;
+        if !same_line || other.quantity.unit() != unit {
+            return None;
+        }
+        let milli = self.quantity.milli().checked_add(other.quantity.milli())?;
+        Some(OrderDetail {
+            quantity: Quantity::new(milli, unit),
+            ..self.clone()
+        })
+    }
+}
+
+impl PayOrdersDetail {
+    /// fails like `replace_value` would, without changing anything
+    fn check_replace_value(
+        &self,
+        payid: &PaymentId,
+        kind: ValueKind,
+        from: &CustomString,
+        to: &CustomString,
+    ) -> Result<()> {
+        match kind {
+            ValueKind::Method => {
+                let merged = (self.payment_details.allocations.iter())
+                    .filter(|allocation| &allocation.method == from || &allocation.method == to)
+                    .map(|allocation| allocation.amount);
+                Money::checked_sum(merged).map(|_| ())
+            }
+            ValueKind::Item => {
+                let existing = self.orders.get(&OrderId::new(to.clone()));
+                let moved = self.orders.get(&OrderId::new(from.clone()));
+                match existing.zip(moved) {
+                    Some((existing, moved)) => {
+                        existing.combine(moved).map(|_| ()).ok_or_else(|| {
+                            Error::OrderCollision(payid.clone(), OrderId::new(to.clone()))
+                        })
+                    }
+                    None => Ok(()),
+                }
+            }
+            ValueKind::City | ValueKind::Shop => Ok(()),
+        }
+    }
+
+    /// orders that end up on the same item are combined, see
+    /// `OrderDetail::combine`, otherwise it fails with `OrderCollision`
+    pub(super) fn replace_value(
+        &mut self,
+        payid: &PaymentId,
+        kind: ValueKind,
+        from: &CustomString,
+        to: &CustomString,
+    ) -> Result<()> {
+        self.payment_details.replace_value(kind, from, to)?;
+        if kind != ValueKind::Item {
+            return Ok(());
+        }
+        let Some(moved) = self.orders.remove(&OrderId::new(from.clone())) else {
+            return Ok(());
+        };
+        let orderid = OrderId::new(to.clone());
+        let combined = match self.orders.get(&orderid) {
+            Some(existing) => existing
+                .combine(&moved)
+                .ok_or_else(|| Error::OrderCollision(payid.clone(), orderid.clone()))?,
+            None => moved,
+        };
+        self.orders.insert(orderid, combined);
+        Ok(())
+    }
+}
+
+impl PayOrdersDetail {
+    fn values(&self, kind: ValueKind) -> BTreeSet<&CustomString> {
+        let details = &self.payment_details;
+        match kind {
+            ValueKind::City => BTreeSet::from([&details.city]),
+            ValueKind::Shop => BTreeSet::from([&details.shop]),
+            ValueKind::Method => (details.allocations.iter())
+                .map(|allocation| &allocation.method)
+                .chain([&details.method])
+                .collect(),
+            ValueKind::Item => self.orders.keys().map(|orderid| &orderid.item).collect(),
+        }
+    }
+}
+
+impl BudgetScope {
+    fn value(&self, kind: ValueKind) -> Option<&CustomString> {
+        match (self, kind) {
+            (BudgetScope::Shop(shop), ValueKind::Shop) => Some(shop),
+            (BudgetScope::Method(method), ValueKind::Method) => Some(method),
+            (BudgetScope::City(city), ValueKind::City) => Some(city),
+            _ => None,
+        }
+    }
+
+    fn replace_value(&mut self, kind: ValueKind, from: &CustomString, to: &CustomString) {
+        match (self, kind) {
+            (BudgetScope::Shop(shop), ValueKind::Shop) => replace(shop, from, to),
+            (BudgetScope::Method(method), ValueKind::Method) => replace(method, from, to),
+            (BudgetScope::City(city), ValueKind::City) => replace(city, from, to),
+            _ => {}
+        }
+    }
+}
+
+impl AllPayments {
+    /// references to every value of `kind` that is in use
+    pub fn value_references(&self, kind: ValueKind) -> BTreeMap<CustomString, ValueReferences> {
+        let mut references = BTreeMap::<CustomString, ValueReferences>::new();
+        for (payid, payment) in &self.payments {
+            for value in payment.values(kind) {
+                let payments = &mut references.entry(value.clone()).or_default().payments;
+                payments.push(payid.clone());
+            }
+        }
+        for (name, template) in &self.recurring {
+            for value in template.payment().values(kind) {
+                let recurring = &mut references.entry(value.clone()).or_default().recurring;
+                recurring.push(name.clone());
+            }
+        }
+        for (name, budget) in &self.budgets {
+            if let Some(value) = budget.scope().value(kind) {
+                let budgets = &mut references.entry(value.clone()).or_default().budgets;
+                budgets.push(name.clone());
+            }
+        }
+        if kind == ValueKind::Method {
+            for (incomeid, income) in &self.incomes {
+                let incomes = &mut references
+                    .entry(income.account().clone())
+                    .or_default()
+                    .incomes;
+                incomes.push(incomeid.clone());
+            }
+            for (transferid, transfer) in &self.transfers {
+                for method in BTreeSet::from([transfer.from(), transfer.to()]) {
+                    let transfers = &mut references.entry(method.clone()).or_default().transfers;
+                    transfers.push(transferid.clone());
+                }
+            }
+            for method in self.accounts.keys() {
+                references.entry(method.clone()).or_default().account = true;
+            }
+        }
+        references
+    }
+
+    /// removes a value nothing refers to anymore, otherwise fails with
+    /// `ValueInUse` listing the references
+    pub fn remove_value(&mut self, kind: ValueKind, value: &CustomString) -> Result<()> {
+        if !self.value_set.values(kind).contains(value) {
+            return Err(ValueSet::missing(kind, value));
+        }
+        if let Some(references) = self.value_references(kind).remove(value) {
+            return Err(Error::ValueInUse(kind, value.clone(), Box::new(references)));
+        }
+        self.value_set.values_mut(kind).remove(value);
+        self.categories.remove_value(kind, value);
+        Ok(())
+    }
+
+    /// removes every value of `values`: a city, shop, method or item still
+    /// referred to fails with `ValueInUse`, any other value still used with
+    /// `MissingElements`; nothing changes on error
+    pub fn remove_values(&mut self, values: &ValueSet) -> Result<()> {
+        let mut categories = self.categories.clone();
+        for kind in ValueKind::ALL {
+            let mut references = self.value_references(kind);
+            for value in values.values(kind) {
+                if let Some(references) = references.remove(value) {
+                    return Err(Error::ValueInUse(kind, value.clone(), Box::new(references)));
+                }
+                categories.remove_value(kind, value);
+            }
+        }
+
+        let mut remaining = self.value_set.clone();
+        remaining.remove_all(values);
+        for payment in self.payments.values() {
+            payment.check_missing_elements(&remaining)?;
+        }
+        for template in self.recurring.values() {
+            template.payment().check_missing_elements(&remaining)?;
+        }
+        for budget in self.budgets.values() {
+            budget.scope().check_missing_elements(&remaining)?;
+        }
+        for income in self.incomes.values() {
+            income.check_missing_elements(&remaining)?;
+        }
+        for transfer in self.transfers.values() {
+            transfer.check_missing_elements(&remaining)?;
+        }
+        categories.check_missing_elements(&remaining)?;
+
+        self.value_set = remaining;
+        self.categories = categories;
+        Ok(())
+    }
+
+    /// removes every city, shop, method and item nothing refers to, and
+    /// returns them
+    pub fn prune_unused_values(&mut self) -> ValueSet {
+        let mut pruned = ValueSet::new();
+        for kind in ValueKind::ALL {
+            let references = self.value_references(kind);
+            let unused: BTreeSet<CustomString> = (self.value_set.values(kind).iter())
+                .filter(|value| !references.contains_key(*value))
+                .cloned()
+                .collect();
+            for value in &unused {
+                self.value_set.values_mut(kind).remove(value);
+                self.categories.remove_value(kind, value);
+            }
+            *pruned.values_mut(kind) = unused;
+        }
+        pruned
+    }
+
+    /// renames the value everywhere it is used, `to` must be a new value
+    pub fn rename_value(
+        &mut self,
+        kind: ValueKind,
+        from: &CustomString,
+        to: CustomString,
+    ) -> Result<()> {
+        if self.value_set.values(kind).contains(&to) {
+            return Err(Error::ValueDuplicated(kind, to));
+        }
+        self.replace_value(kind, from, &to)
+    }
+
+    /// replaces `from` by the existing `into` everywhere and drops `from`;
+    /// nothing changes if any payment can't be merged
+    pub fn merge_values(
+        &mut self,
+        kind: ValueKind,
+        from: &CustomString,
+        into: &CustomString,
+    ) -> Result<()> {
+        if !self.value_set.values(kind).contains(into) {
+            return Err(ValueSet::missing(kind, into));
+        }
+        self.replace_value(kind, from, into)
+    }
+
+    fn replace_value(
+        &mut self,
+        kind: ValueKind,
+        from: &CustomString,
+        to: &CustomString,
+    ) -> Result<()> {
+        if !self.value_set.values(kind).contains(from) {
+            return Err(ValueSet::missing(kind, from));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if kind == ValueKind::Method
+            && self.accounts.contains_key(from)
+            && self.accounts.contains_key(to)
+        {
+            return Err(Error::AccountDuplicated(to.clone()));
+        }
+
+        // everything is checked before the first change, so a failure leaves
+        // the dataset untouched
+        for (payid, payment) in &self.payments {
+            payment.check_replace_value(payid, kind, from, to)?;
+        }
+        for template in self.recurring.values() {
+            let payid = PaymentId::new(*template.start());
+            (template.payment()).check_replace_value(&payid, kind, from, to)?;
+        }
+        if kind == ValueKind::Method
+            && (self.transfers.values()).any(|transfer| {
+                let ends = [transfer.from(), transfer.to()];
+                ends.contains(&from) && ends.contains(&to)
+            })
+        {
+            return Err(Error::SameAccountTransfer(to.clone()));
+        }
+
+        let values = self.value_set.values_mut(kind);
+        values.remove(from);
+        values.insert(to.clone());
+        for (payid, payment) in &mut self.payments {
+            payment.replace_value(payid, kind, from, to)?;
+        }
+        for template in self.recurring.values_mut() {
+            let payid = PaymentId::new(*template.start());
+            template
+                .payment_mut()
+                .replace_value(&payid, kind, from, to)?;
+        }
+        for budget in self.budgets.values_mut() {
+            budget.scope_mut().replace_value(kind, from, to);
+        }
+        self.categories.replace_value(kind, from, to);
+        if kind == ValueKind::Method {
+            if let Some(account) = self.accounts.remove(from) {
+                self.accounts.insert(to.clone(), account);
+            }
+            for income in self.incomes.values_mut() {
+                income.replace_method(from, to);
+            }
+            for transfer in self.transfers.values_mut() {
+                transfer.replace_method(from, to);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueKind;
+    use crate::error::Error;
+    use crate::{
+        payments::{
+            AllPayments, Budget, BudgetPeriod, BudgetScope, OrderDetail, OrderId, PaymentDetail,
+            PaymentId, ValueSet,
+        },
+        time::FakeUtcTime,
+        types::{internment::CustomString, money::Money},
+    };
+
+    #[test]
+    fn rename_and_merge() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Milan")],
+            vec![
+                CustomString::from("Esselunga"),
+                CustomString::from("Esselunga Via Roma"),
+            ],
+            vec![CustomString::from("Cash")],
+            vec![
+                CustomString::from("Milk"),
+                CustomString::from("Mlik"),
+                CustomString::from("Eggs"),
+            ],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let budget = Budget::new(
+            BudgetScope::Shop("Esselunga Via Roma".into()),
+            BudgetPeriod::Monthly,
+            Money::new(10_000),
+        );
+        all_payments.add_budget("groceries".into(), budget).unwrap();
+
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let paydetail =
+            PaymentDetail::new("Milan".into(), "Esselunga Via Roma".into(), "Cash".into());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+        for (item, price, quantity) in [("Milk", 150, 1), ("Mlik", 150, 2), ("Eggs", 300, 1)] {
+            let orderdetail = OrderDetail::new(Money::new(price), quantity);
+            all_payments
+                .add_order(&payid, OrderId::new(item.into()), orderdetail)
+                .unwrap();
+        }
+
+        let shop = CustomString::from("Esselunga Via Roma");
+        assert!(
+            all_payments
+                .rename_value(ValueKind::Shop, &shop, "Esselunga".into())
+                .is_err()
+        );
+        all_payments
+            .merge_values(ValueKind::Shop, &shop, &"Esselunga".into())
+            .unwrap();
+        assert!(!all_payments.value_set().shops().contains(&shop));
+        assert_eq!(
+            all_payments.payments()[&payid]
+                .payment_details()
+                .shop()
+                .as_str(),
+            "Esselunga"
+        );
+        assert_eq!(
+            all_payments.budgets()[&CustomString::from("groceries")].scope(),
+            &BudgetScope::Shop("Esselunga".into())
+        );
+
+        all_payments
+            .merge_values(ValueKind::Item, &"Mlik".into(), &"Milk".into())
+            .unwrap();
+        let milk = &all_payments.payments()[&payid].orders()[&OrderId::new("Milk".into())];
+        assert_eq!(milk.quantity().milli(), 3_000);
+
+        // eggs can't be combined with milk at a different price
+        let before = all_payments.clone();
+        assert!(
+            all_payments
+                .merge_values(ValueKind::Item, &"Eggs".into(), &"Milk".into())
+                .is_err()
+        );
+        assert_eq!(all_payments, before);
+    }
+
+    #[test]
+    fn remove_and_prune() {
+        let mut values = ValueSet::new();
+        values.add_values(
+            vec![CustomString::from("Milan"), CustomString::from("Turin")],
+            vec![CustomString::from("Esselunga")],
+            vec![CustomString::from("Cash"), CustomString::from("Cheque")],
+            vec![CustomString::from("Milk")],
+        );
+        let mut all_payments = AllPayments::new();
+        all_payments.add_values(values);
+        let payid = PaymentId::new(FakeUtcTime::parse_str("2024/01/10 10:00").unwrap());
+        let paydetail = PaymentDetail::new("Milan".into(), "Esselunga".into(), "Cash".into());
+        all_payments.add_payment(payid.clone(), paydetail).unwrap();
+
+        let milan = CustomString::from("Milan");
+        match all_payments.remove_value(ValueKind::City, &milan) {
+            Err(Error::ValueInUse(ValueKind::City, _, references)) => {
+                assert_eq!(references.payments(), &vec![payid.clone()]);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        all_payments
+            .remove_value(ValueKind::City, &"Turin".into())
+            .unwrap();
+        assert!(
+            all_payments
+                .remove_value(ValueKind::City, &"Turin".into())
+                .is_err()
+        );
+
+        let pruned = all_payments.prune_unused_values();
+        assert_eq!(
+            pruned
+                .methods()
+                .iter()
+                .map(CustomString::as_str)
+                .collect::<Vec<_>>(),
+            vec!["Cheque"]
+        );
+        assert_eq!(
+            pruned
+                .items()
+                .iter()
+                .map(CustomString::as_str)
+                .collect::<Vec<_>>(),
+            vec!["Milk"]
+        );
+        assert!(pruned.cities().is_empty() && pruned.shops().is_empty());
+        assert!(all_payments.prune_unused_values().is_empty());
+    }
+}
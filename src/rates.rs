@@ -0,0 +1,170 @@
+use crate::{
+    error::{Error, Result},
+    fs::read_file,
+    time::FakeUtcTime,
+    types::{currency::Currency, internment::CustomString, money::Money},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+pub const RATE_SCALE: u64 = 1_000_000;
+
+/// Dated exchange rates, stored as millionths of the target currency per
+/// unit of the source currency. A rate stays in effect until the next one.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ExchangeRates {
+    rates: BTreeMap<(Currency, Currency), BTreeMap<FakeUtcTime, u64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct RateJson {
+    date: CustomString,
+    from: Currency,
+    to: Currency,
+    rate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExchangeRatesJson {
+    rates: Vec<RateJson>,
+}
+
+impl ExchangeRatesJson {
+    pub fn from_api(rates: &ExchangeRates) -> Result<Self> {
+        let mut rates_json = vec![];
+        for ((from, to), dated) in &rates.rates {
+            for (date, rate) in dated {
+                rates_json.push(RateJson {
+                    date: date.format_str()?.into(),
+                    from: *from,
+                    to: *to,
+                    rate: *rate as f64 / RATE_SCALE as f64,
+                });
+            }
+        }
+        Ok(Self { rates: rates_json })
+    }
+
+    pub fn to_api(&self) -> Result<ExchangeRates> {
+        let mut rates = ExchangeRates::new();
+        for rate in &self.rates {
+            let date = FakeUtcTime::parse_str(rate.date.as_str())?;
+            let scaled = (rate.rate * RATE_SCALE as f64).round();
+            if !scaled.is_finite() || scaled <= 0.0 {
+                return Err(Error::InvalidExchangeRate(rate.from, rate.to, date));
+            }
+            rates.add_rate(rate.from, rate.to, date, scaled as u64)?;
+        }
+        Ok(rates)
+    }
+}
+
+impl ExchangeRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self> {
+        let rates_json: ExchangeRatesJson =
+            serde_json::from_str(json_str).map_err(Error::JsonParseFailed)?;
+        rates_json.to_api()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = read_file(path)?;
+        Self::from_json(&String::from_utf8(data).map_err(Error::from_generic)?)
+    }
+
+    /// `rate` must be positive and fit an `i64`, a zero rate could not be
+    /// converted back
+    pub fn add_rate(
+        &mut self,
+        from: Currency,
+        to: Currency,
+        date: FakeUtcTime,
+        rate: u64,
+    ) -> Result<()> {
+        if rate == 0 || i64::try_from(rate).is_err() {
+            return Err(Error::InvalidExchangeRate(from, to, date));
+        }
+        self.rates.entry((from, to)).or_default().insert(date, rate);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rates.is_empty()
+    }
+
+    fn latest(&self, from: Currency, to: Currency, date: FakeUtcTime) -> Option<u64> {
+        self.rates
+            .get(&(from, to))
+            .and_then(|dated| dated.range(..=date).next_back())
+            .map(|(_, rate)| *rate)
+    }
+
+    pub fn convert(
+        &self,
+        amount: Money,
+        from: Currency,
+        to: Currency,
+        date: FakeUtcTime,
+    ) -> Result<Money> {
+        if from == to {
+            return Ok(amount);
+        }
+        if let Some(rate) = self.latest(from, to, date) {
+            return amount.checked_scale(rate as i64, RATE_SCALE as i64);
+        }
+        if let Some(rate) = self.latest(to, from, date) {
+            return amount.checked_scale(RATE_SCALE as i64, rate as i64);
+        }
+        Err(Error::ExchangeRateMissing(from, to, date))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExchangeRates;
+    use crate::{
+        time::FakeUtcTime,
+        types::{currency::Currency, money::Money},
+    };
+
+    #[test]
+    fn dated_conversion() {
+        let json_string = r#"
+{ "rates": [
+    { "date": "2024/01/01 00:00", "from": "USD", "to": "EUR", "rate": 0.9 },
+    { "date": "2024/02/01 00:00", "from": "USD", "to": "EUR", "rate": 0.8 } ] }
+        "#;
+        let rates = ExchangeRates::from_json(json_string).unwrap();
+        let usd = Currency::new("USD").unwrap();
+        let eur = Currency::new("EUR").unwrap();
+        let gbp = Currency::new("GBP").unwrap();
+        let january = FakeUtcTime::parse_str("2024/01/15 12:00").unwrap();
+        let february = FakeUtcTime::parse_str("2024/02/15 12:00").unwrap();
+        let before = FakeUtcTime::parse_str("2023/12/31 12:00").unwrap();
+
+        let amount = Money::new(1000);
+        assert_eq!(
+            rates.convert(amount, usd, eur, january).unwrap(),
+            900.into()
+        );
+        assert_eq!(
+            rates.convert(amount, usd, eur, february).unwrap(),
+            800.into()
+        );
+        assert_eq!(
+            rates.convert(amount, eur, usd, february).unwrap(),
+            1250.into()
+        );
+        assert_eq!(rates.convert(amount, gbp, gbp, before).unwrap(), amount);
+        let refund = Money::new(-1001);
+        assert_eq!(
+            rates.convert(refund, usd, eur, january).unwrap(),
+            (-901).into()
+        );
+        assert!(rates.convert(amount, usd, eur, before).is_err());
+        assert!(rates.convert(amount, gbp, eur, january).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-#![deprecated]
+// not deprecated in test builds, so the unit tests below can call into it
+#![cfg_attr(not(test), deprecated)]
 
 use crossterm::style::{Color, Stylize};
 use derive_getters::Getters;
@@ -8,8 +9,12 @@ const COLOR_GOOD: Color = Color::DarkGreen;
 const COLOR_BAD: Color = Color::DarkRed;
 const COLOR_EMPTY: Color = Color::DarkGrey;
 const COLOR_CUTOUT: Color = Color::Yellow;
+const COLOR_CREDIT: Color = Color::DarkBlue;
+const COLOR_PROJECTED: Color = Color::DarkCyan;
+const COLOR_BAND: Color = Color::Cyan;
 
 const STR_EMPTY: &str = " ";
+const STR_BAND: &str = "░";
 
 #[derive(Getters, Debug)]
 pub struct Graph {
@@ -21,12 +26,21 @@ pub struct Graph {
 
 #[derive(Debug, PartialEq, Eq)]
 struct Downscaled {
-    values: Vec<u32>,
+    values: Vec<i64>,
     ignored: Vec<u32>,
+    projected: Vec<u32>,
+    band: Vec<(i64, i64)>,
     factor: u32,
 }
 
-fn downscale_to_biggest_factor(values: &[u32], ignored: &[u32], max_length: u32) -> Downscaled {
+/// a compacted value is projected when any of its values is, its band is the
+/// sum of the bands of its values
+fn downscale_to_biggest_factor(
+    values: &[i64],
+    ignored: &[u32],
+    (projected, band): (&[u32], &[(i64, i64)]),
+    max_length: u32,
+) -> Downscaled {
     let max_length = max_length as usize;
     let mut scaling_factor = values.len() / max_length;
     while scaling_factor * max_length < values.len() {
@@ -36,23 +50,36 @@ fn downscale_to_biggest_factor(values: &[u32], ignored: &[u32], max_length: u32)
     let final_len = values.len().div_ceil(scaling_factor);
     let mut compacted_values = Vec::with_capacity(final_len);
     let mut compacted_ignored = Vec::with_capacity(final_len);
-    let mut buffer = Vec::<u32>::with_capacity(scaling_factor);
+    let mut compacted_projected = Vec::with_capacity(final_len);
+    let mut compacted_band = Vec::with_capacity(if band.is_empty() { 0 } else { final_len });
+    let mut buffer = Vec::<i64>::with_capacity(scaling_factor);
 
     for i in 0..final_len {
+        let mut is_projected = false;
+        let mut band_sum = (0, 0);
         for j in 0..scaling_factor {
             let index = scaling_factor * i + j;
-            if !ignored.contains(&(index as u32)) {
-                if let Some(&elem) = values.get(index) {
-                    buffer.push(elem);
-                }
+            is_projected |= projected.contains(&(index as u32));
+            if !ignored.contains(&(index as u32))
+                && let Some(&elem) = values.get(index)
+            {
+                buffer.push(elem);
+                let (low, high) = band.get(index).copied().unwrap_or((elem, elem));
+                band_sum = (band_sum.0 + low, band_sum.1 + high);
             }
         }
 
+        if !band.is_empty() {
+            compacted_band.push(band_sum);
+        }
         if buffer.is_empty() {
             compacted_ignored.push(i as u32);
             compacted_values.push(0);
         } else {
             compacted_values.push(buffer.iter().sum());
+            if is_projected {
+                compacted_projected.push(i as u32);
+            }
         }
         buffer.clear();
     }
@@ -60,16 +87,69 @@ fn downscale_to_biggest_factor(values: &[u32], ignored: &[u32], max_length: u32)
     Downscaled {
         values: compacted_values,
         ignored: compacted_ignored,
+        projected: compacted_projected,
+        band: compacted_band,
         factor: scaling_factor as u32,
     }
 }
 
+/// a projected bar over its band: solid up to `low_len`, lighter up to the
+/// value at `bar_len` and shaded up to `high_len`, with the cutout marker
+fn band_line(
+    bar_len: usize,
+    low_len: usize,
+    high_len: usize,
+    width: usize,
+    cutout_line: Option<usize>,
+) -> String {
+    (0..width)
+        .map(|cell| {
+            let cell = match cell {
+                cell if Some(cell) == cutout_line => STR_EMPTY.on(COLOR_CUTOUT),
+                cell if cell < low_len => STR_EMPTY.on(COLOR_PROJECTED),
+                cell if cell < bar_len => STR_EMPTY.on(COLOR_BAND),
+                cell if cell < high_len => STR_BAND.with(COLOR_BAND),
+                _ => STR_EMPTY.stylize(),
+            };
+            cell.to_string()
+        })
+        .collect()
+}
+
+fn format_cents(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    format!("{sign}{}.{:02}", value / 100, value % 100)
+}
+
 pub fn bar_graph_horizontal(
-    values: &[u32],
+    values: &[i64],
     max_width: u32,
     max_height: u32,
     cutout: f64,
     ignored: &[u32],
+    projected: &[u32],
+) -> Graph {
+    bar_graph_horizontal_opt(
+        values,
+        max_width,
+        max_height,
+        Some(cutout),
+        ignored,
+        (projected, &[]),
+    )
+}
+
+/// horizontal bars, without a cutout marker and no bar drawn as too high when
+/// `cutout` is none; projected bars are drawn over their `(low, high)` band
+/// when `band` has one for each value
+fn bar_graph_horizontal_opt(
+    values: &[i64],
+    max_width: u32,
+    max_height: u32,
+    cutout: Option<f64>,
+    ignored: &[u32],
+    (projected, band): (&[u32], &[(i64, i64)]),
 ) -> Graph {
     if values.is_empty() || max_width == 0 || max_height == 0 {
         return Graph {
@@ -81,28 +161,48 @@ pub fn bar_graph_horizontal(
     }
 
     if values.len() > max_height as usize {
-        let downscaled = downscale_to_biggest_factor(values, ignored, max_height);
-        return bar_graph_horizontal(
+        let downscaled =
+            downscale_to_biggest_factor(values, ignored, (projected, band), max_height);
+        return bar_graph_horizontal_opt(
             &downscaled.values,
             max_width,
             max_height,
-            cutout * downscaled.factor as f64,
+            cutout.map(|cutout| cutout * downscaled.factor as f64),
             &downscaled.ignored,
+            (&downscaled.projected, &downscaled.band),
         );
     }
 
     let mut lines = Vec::with_capacity(max_height as usize);
-    let cutout_u32 = cutout as u32;
-    let max = u32::max(cutout_u32, u32::max(1, *values.iter().max().unwrap_or(&0)));
+    let cutout_u32 = cutout.map(|cutout| cutout as u32);
+    let max_positive = (values.iter().chain(band.iter().map(|(_, high)| high)))
+        .map(|&val| val.max(0).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    // negative bars grow to the left of the zero column
+    let max_negative = values
+        .iter()
+        .map(|&val| val.min(0).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    let max = u64::max(
+        u64::from(cutout_u32.unwrap_or(0)),
+        u64::max(1, max_positive),
+    );
     let len = values.len();
     let actual_height = max_height as usize / len * len;
     let factor = actual_height / len;
-    let unit_width = f64::from(max_width) / f64::from(max);
-    let mut cutout_line = (cutout * unit_width) as usize;
-
-    if cutout_line == max_width as usize && max == cutout_u32 {
-        cutout_line -= 1;
-    }
+    let unit_width = f64::from(max_width) / (max + max_negative) as f64;
+    let negative_width = (max_negative as f64 * unit_width) as usize;
+    let positive_width = max_width as usize - negative_width;
+    let cutout_line = cutout.map(|cutout| {
+        let cutout_line = (cutout * unit_width) as usize;
+        if cutout_line == positive_width && Some(max) == cutout_u32.map(u64::from) {
+            cutout_line - 1
+        } else {
+            cutout_line
+        }
+    });
 
     for (index, &val) in values.iter().enumerate() {
         if ignored.contains(&(index as u32)) {
@@ -113,11 +213,43 @@ pub fn bar_graph_horizontal(
             continue;
         }
         let mut color = COLOR_GOOD;
-        if val >= cutout_u32 {
+        if projected.contains(&(index as u32)) {
+            color = COLOR_PROJECTED;
+        } else if val < 0 {
+            color = COLOR_CREDIT;
+        } else if cutout_u32.is_some_and(|cutout| val >= i64::from(cutout)) {
             color = COLOR_BAD;
         }
-        let bar_len = (f64::from(val) * unit_width).trunc() as usize;
-        let rem_len = max_width as usize - bar_len;
+        let bar_len = (val.unsigned_abs() as f64 * unit_width).trunc() as usize;
+        if val < 0 {
+            let bar_len = bar_len.min(negative_width);
+            let str = format!(
+                "{}{}{}",
+                STR_EMPTY.repeat(negative_width - bar_len),
+                STR_EMPTY.repeat(bar_len).on(color),
+                STR_EMPTY.repeat(positive_width)
+            );
+            for _ in 0..factor {
+                lines.push(str.clone());
+            }
+            continue;
+        }
+        let bar_len = bar_len.min(positive_width);
+        let rem_len = positive_width - bar_len;
+        let zero_offset = STR_EMPTY.repeat(negative_width);
+        if let Some(&(low, high)) = band.get(index)
+            && projected.contains(&(index as u32))
+        {
+            let to_len = |value: i64| (value.max(0) as f64 * unit_width).trunc() as usize;
+            let low_len = to_len(low).min(bar_len);
+            let high_len = to_len(high).clamp(bar_len, positive_width);
+            let cutout_line = cutout_line.filter(|&line| line < positive_width);
+            let line = band_line(bar_len, low_len, high_len, positive_width, cutout_line);
+            for _ in 0..factor {
+                lines.push(format!("{zero_offset}{line}"));
+            }
+            continue;
+        }
         for _ in 0..factor {
             let tmp: &str = " ";
             // if factor == 1 {
@@ -133,7 +265,9 @@ pub fn bar_graph_horizontal(
                 STR_EMPTY.repeat(rem_len)
             );
             // add cutout line
-            if cutout_line < max_width as usize {
+            if let Some(cutout_line) = cutout_line
+                && cutout_line < positive_width
+            {
                 if cutout_line.cmp(&bar_len) == std::cmp::Ordering::Less {
                     str = format!(
                         "{}{}{}{}",
@@ -156,24 +290,68 @@ pub fn bar_graph_horizontal(
                     );
                 }
             }
-            lines.push(str.clone());
+            lines.push(format!("{zero_offset}{str}"));
         }
     }
 
     Graph {
         area: lines,
-        cutout: cutout_u32,
+        cutout: cutout_u32.unwrap_or(0),
         factor: factor as u32,
         unit_length: unit_width,
     }
 }
 
 pub fn bar_graph_horizontal_label(
-    values: &[u32],
+    values: &[i64],
+    max_width: u32,
+    max_height: u32,
+    cutout: f64,
+    ignored: &[u32],
+    projected: &[u32],
+    currency: &str,
+) -> Graph {
+    bar_graph_horizontal_label_opt(
+        values,
+        max_width,
+        max_height,
+        Some(cutout),
+        ignored,
+        (projected, &[]),
+        currency,
+    )
+}
+
+/// labelled bars where each projected value is drawn over its `(low, high)`
+/// band, `band` has an entry for every value
+pub fn bar_graph_horizontal_label_band(
+    values: &[i64],
     max_width: u32,
     max_height: u32,
     cutout: f64,
     ignored: &[u32],
+    (projected, band): (&[u32], &[(i64, i64)]),
+    currency: &str,
+) -> Graph {
+    bar_graph_horizontal_label_opt(
+        values,
+        max_width,
+        max_height,
+        Some(cutout),
+        ignored,
+        (projected, band),
+        currency,
+    )
+}
+
+fn bar_graph_horizontal_label_opt(
+    values: &[i64],
+    max_width: u32,
+    max_height: u32,
+    cutout: Option<f64>,
+    ignored: &[u32],
+    (projected, band): (&[u32], &[(i64, i64)]),
+    currency: &str,
 ) -> Graph {
     const MIN_GRAPH_SIZE: usize = 3;
 
@@ -187,30 +365,40 @@ pub fn bar_graph_horizontal_label(
     }
 
     if (max_height as usize) < values.len() {
-        let downscaled = downscale_to_biggest_factor(values, ignored, max_height);
-        return bar_graph_horizontal_label(
+        let downscaled =
+            downscale_to_biggest_factor(values, ignored, (projected, band), max_height);
+        return bar_graph_horizontal_label_opt(
             &downscaled.values,
             max_width,
             max_height,
-            cutout * downscaled.factor as f64,
+            cutout.map(|cutout| cutout * downscaled.factor as f64),
             &downscaled.ignored,
+            (&downscaled.projected, &downscaled.band),
+            currency,
         );
     }
 
     let max_index_len = values.len().to_string().len();
     let left_len = max_index_len + 2;
 
-    let max_value = format!("{:03}", values.iter().max().unwrap_or(&0));
-    let len = max_value.len();
-    let max_value = format!("{}.{}", &max_value[..len - 2], &max_value[len - 2..]);
-    let max_value_len = max_value.len() + 1;
+    let values_fmt: Vec<String> = values.iter().map(|&val| format_cents(val)).collect();
+    let max_value = values_fmt.iter().map(String::len).max().unwrap_or(0);
+    let currency_len = currency.chars().count();
+    let max_value_len = max_value + currency_len;
     let right_len = max_value_len + 2;
 
     let label_len = left_len + right_len;
-    let cutout_u32 = cutout as u32;
+    let cutout_u32 = cutout.map(|cutout| cutout as u32);
 
     if (max_width as usize) < label_len + MIN_GRAPH_SIZE {
-        return bar_graph_horizontal(values, max_width, max_height, cutout, ignored);
+        return bar_graph_horizontal_opt(
+            values,
+            max_width,
+            max_height,
+            cutout,
+            ignored,
+            (projected, band),
+        );
     }
 
     let factor = (max_height as usize) / values.len();
@@ -218,24 +406,31 @@ pub fn bar_graph_horizontal_label(
     let cached_right = STR_EMPTY.repeat(right_len);
 
     let actual_max_width = max_width as usize - label_len;
-    let mut graph =
-        bar_graph_horizontal(values, actual_max_width as u32, max_height, cutout, ignored);
-    let e1 = "----€ ".with(COLOR_EMPTY).bold();
+    let mut graph = bar_graph_horizontal_opt(
+        values,
+        actual_max_width as u32,
+        max_height,
+        cutout,
+        ignored,
+        (projected, band),
+    );
+    let e1 = format!("----{currency} ").with(COLOR_EMPTY).bold();
     let e2 = "- ".with(COLOR_EMPTY).bold();
-    let e3 = STR_EMPTY.repeat(right_len - 6);
+    let e3 = STR_EMPTY.repeat(right_len - (currency_len + 5));
     let e4 = STR_EMPTY.repeat(left_len - 2);
     for (index, &value) in values.iter().enumerate() {
         let mut color = COLOR_GOOD;
-        if value >= cutout_u32 {
+        if projected.contains(&(index as u32)) {
+            color = COLOR_PROJECTED;
+        } else if value < 0 {
+            color = COLOR_CREDIT;
+        } else if cutout_u32.is_some_and(|cutout| value >= i64::from(cutout)) {
             color = COLOR_BAD;
         }
         let index_fmt = format!(" {index:>max_index_len$} ",)
             .with(COLOR_TEXT)
             .bold();
-        let len = max_value.len();
-        let tmp_fmt = format!("{:>len$}", format!("{:03}", values[index]));
-        let tmp = tmp_fmt.len() - 2;
-        let value_fmt = format!("{}.{}€ ", &tmp_fmt[..tmp], &tmp_fmt[tmp..]);
+        let value_fmt = format!(" {:>max_value$}{currency} ", values_fmt[index]);
         let value_fmt = value_fmt.with(color).bold();
         if let Some(line) = graph.area.get_mut(index * factor) {
             if ignored.contains(&(index as u32)) {
@@ -254,18 +449,71 @@ pub fn bar_graph_horizontal_label(
     graph
 }
 
+/// two labelled graphs next to each other, e.g. income on the left and
+/// expenses on the right, each half gets the same width and its own cutout,
+/// a side without one is drawn without a marker
+pub fn bar_graph_side_by_side(
+    left: &[i64],
+    right: &[i64],
+    max_width: u32,
+    max_height: u32,
+    (left_cutout, right_cutout): (Option<f64>, Option<f64>),
+    ignored: &[u32],
+    currency: &str,
+) -> Graph {
+    let half_width = max_width.saturating_sub(1) / 2;
+    let left_graph = bar_graph_horizontal_label_opt(
+        left,
+        half_width,
+        max_height,
+        left_cutout,
+        ignored,
+        (&[], &[]),
+        currency,
+    );
+    let right_graph = bar_graph_horizontal_label_opt(
+        right,
+        half_width,
+        max_height,
+        right_cutout,
+        ignored,
+        (&[], &[]),
+        currency,
+    );
+    let empty_left = STR_EMPTY.repeat(half_width as usize);
+
+    let lines = usize::max(left_graph.area.len(), right_graph.area.len());
+    let area = (0..lines)
+        .map(|index| {
+            let left_line = left_graph.area.get(index).unwrap_or(&empty_left);
+            let right_line = right_graph.area.get(index).map_or("", String::as_str);
+            format!("{left_line}{STR_EMPTY}{right_line}")
+        })
+        .collect();
+
+    Graph {
+        area,
+        cutout: right_graph.cutout,
+        factor: right_graph.factor,
+        unit_length: right_graph.unit_length,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        Downscaled, bar_graph_horizontal, bar_graph_horizontal_label, downscale_to_biggest_factor,
+        COLOR_BAND, COLOR_PROJECTED, Downscaled, STR_BAND, STR_EMPTY, bar_graph_horizontal,
+        bar_graph_horizontal_label, bar_graph_horizontal_label_band,
+        bar_graph_horizontal_label_opt, bar_graph_side_by_side, downscale_to_biggest_factor,
     };
+    use crossterm::style::{Color, SetBackgroundColor, Stylize};
 
     #[test]
     pub fn downscale_data() {
         let data = [1, 3, 5, 9, 10];
         assert_eq!(
             vec![4, 14, 10],
-            downscale_to_biggest_factor(&data, &[], 4).values
+            downscale_to_biggest_factor(&data, &[], (&[], &[]), 4).values
         );
 
         let data = [1, 3, 5, 0, 0, 7, 10, 0, 1];
@@ -273,15 +521,27 @@ mod tests {
         let expected = Downscaled {
             values: vec![4, 0, 7, 10, 1],
             ignored: vec![1],
+            projected: vec![4],
+            band: vec![],
             factor: 2,
         };
-        assert_eq!(expected, downscale_to_biggest_factor(&data, &ignored, 5));
+        assert_eq!(
+            expected,
+            downscale_to_biggest_factor(&data, &ignored, (&[8], &[]), 5)
+        );
+
+        let band: Vec<(i64, i64)> = data.iter().map(|&value| (value - 1, value + 1)).collect();
+        let downscaled = downscale_to_biggest_factor(&data, &ignored, (&[8], &band), 5);
+        assert_eq!(
+            downscaled.band,
+            vec![(2, 6), (0, 0), (5, 9), (9, 11), (0, 2)]
+        );
     }
 
     #[test]
     pub fn horizontal_bar_chart() {
         let data = [1, 3, 5, 9, 10, 13, 15];
-        let graph = bar_graph_horizontal(&data, 20, 10, 10.0, &[]);
+        let graph = bar_graph_horizontal(&data, 20, 10, 10.0, &[], &[]);
         assert_eq!(graph.area.len(), 7);
         println!("\n{}", graph.area.join("\n"));
     }
@@ -289,8 +549,76 @@ mod tests {
     #[test]
     pub fn horizontal_bar_chart_label() {
         let data = [1, 3, 5, 9, 10, 13, 15];
-        let graph = bar_graph_horizontal_label(&data, 20, 10, 10.0, &[]);
+        let graph = bar_graph_horizontal_label(&data, 20, 10, 10.0, &[], &[], "€");
         assert_eq!(graph.area.len(), 7);
         println!("\n{}", graph.area.join("\n"));
+
+        let graph = bar_graph_horizontal_label(&data, 20, 10, 10.0, &[2], &[5, 6], "USD");
+        assert_eq!(graph.area.len(), 7);
+        let projected = SetBackgroundColor(COLOR_PROJECTED).to_string();
+        assert!(!graph.area[4].contains(&projected));
+        assert!(graph.area[5].contains(&projected) && graph.area[6].contains(&projected));
+        println!("\n{}", graph.area.join("\n"));
+    }
+
+    #[test]
+    pub fn horizontal_bar_chart_band() {
+        let data = [10, 20, 20, 20];
+        let band = [(10, 10), (20, 20), (10, 30), (20, 20)];
+        let graph =
+            bar_graph_horizontal_label_band(&data, 40, 4, 100.0, &[], (&[2, 3], &band), "€");
+        assert_eq!(graph.area.len(), 4);
+        let projected = SetBackgroundColor(COLOR_PROJECTED).to_string();
+        let band_color = SetBackgroundColor(COLOR_BAND).to_string();
+        // only the projected bar with a wide band gets the lighter part and
+        // the shade up to its high value
+        assert!(!graph.area[1].contains(&projected));
+        assert!(graph.area[2].contains(&projected) && graph.area[2].contains(&band_color));
+        assert!(graph.area[2].contains(STR_BAND));
+        assert!(graph.area[3].contains(&projected));
+        assert!(!graph.area[3].contains(&band_color) && !graph.area[3].contains(STR_BAND));
+        println!("\n{}", graph.area.join("\n"));
+    }
+
+    #[test]
+    pub fn horizontal_bar_chart_negative() {
+        let data = [1, -3, 5, 9, -10, 13, 0];
+        let graph = bar_graph_horizontal(&data, 23, 10, 10.0, &[], &[]);
+        assert_eq!(graph.area.len(), 7);
+        // positive bars start after the zero column, negative ones end there
+        // and have no cutout marker
+        let cutout_marker = STR_EMPTY.on(Color::Yellow).to_string();
+        assert!(graph.area[0].starts_with(&STR_EMPTY.repeat(10)));
+        assert!(graph.area[0].contains(&cutout_marker));
+        assert!(!graph.area[4].starts_with(STR_EMPTY));
+        assert!(!graph.area[4].contains(&cutout_marker));
+        println!("\n{}", graph.area.join("\n"));
+
+        let graph = bar_graph_horizontal_label(&data, 30, 10, 10.0, &[], &[], "€");
+        assert_eq!(graph.area.len(), 7);
+        assert!(graph.area[4].contains("-0.10€"));
+        println!("\n{}", graph.area.join("\n"));
+    }
+
+    #[test]
+    pub fn side_by_side_bar_chart() {
+        let income = [0, 2000, 0, 0, 500];
+        let expenses = [1, 3, 5, 9, 10];
+        let graph =
+            bar_graph_side_by_side(&income, &expenses, 61, 10, (None, Some(10.0)), &[], "€");
+        assert_eq!(graph.area.len(), 10);
+        assert!(graph.area[2].contains("20.00€"));
+        assert!(graph.area[2].contains("0.03€"));
+        // only the expenses on the right get a cutout marker, and the income
+        // on the left is never drawn as too high
+        let cutout_marker = STR_EMPTY.on(Color::Yellow).to_string();
+        let income_graph =
+            bar_graph_horizontal_label_opt(&income, 30, 10, None, &[], (&[], &[]), "€");
+        for (line, income_line) in graph.area.iter().zip(&income_graph.area) {
+            assert!(line.starts_with(income_line.as_str()));
+            assert!(!income_line.contains(&cutout_marker));
+        }
+        assert!(graph.area.iter().any(|line| line.contains(&cutout_marker)));
+        println!("\n{}", graph.area.join("\n"));
     }
 }
@@ -1,8 +1,9 @@
 use crate::error::{Error, Result};
-use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, Utc};
 use derive_getters::Getters;
 
 pub const DEFAULT_FORMAT: &str = "%Y/%m/%d %H:%M";
+pub const SECONDS_PER_DAY: i64 = 86_400;
 
 #[derive(Debug, Getters, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct FakeUtcTime {
@@ -11,6 +12,15 @@ pub struct FakeUtcTime {
 
 pub type FakeUtcFields = DateTime<Utc>;
 
+/// calendar periods, weeks start on monday as in ISO 8601
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
 impl FakeUtcTime {
     pub fn now() -> Self {
         Local::now().naive_local().and_utc().timestamp().into()
@@ -50,6 +60,44 @@ impl FakeUtcTime {
     pub fn format_str(&self) -> Result<String> {
         self.format_str_fmt(DEFAULT_FORMAT)
     }
+
+    pub fn add_days(&self, days: i64) -> Result<Self> {
+        days.checked_mul(SECONDS_PER_DAY)
+            .and_then(|seconds| self.timestamp.checked_add(seconds))
+            .map(Self::from_timestamp)
+            .ok_or(Error::TimeFormatFailed(*self))
+    }
+
+    /// start of the period containing this time
+    pub fn period_start(&self, period: Period) -> Result<Self> {
+        let date = self.get_fields()?.date_naive();
+        let start = match period {
+            Period::Day => Some(date),
+            Period::Week => {
+                date.checked_sub_days(Days::new(date.weekday().num_days_from_monday().into()))
+            }
+            Period::Month => date.with_day(1),
+            Period::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1),
+        };
+        start
+            .and_then(|start| start.and_hms_opt(0, 0, 0))
+            .map(|start| start.and_utc().into())
+            .ok_or(Error::TimeFormatFailed(*self))
+    }
+
+    /// start of the period after the one containing this time
+    pub fn period_end(&self, period: Period) -> Result<Self> {
+        let start = self.period_start(period)?;
+        let start_fields = start.get_fields()?;
+        let end = match period {
+            Period::Day => start_fields.checked_add_days(Days::new(1)),
+            Period::Week => start_fields.checked_add_days(Days::new(7)),
+            Period::Month => start_fields.checked_add_months(Months::new(1)),
+            Period::Year => start_fields.checked_add_months(Months::new(12)),
+        };
+        end.map(Self::from_fields)
+            .ok_or(Error::TimeFormatFailed(start))
+    }
 }
 
 impl From<i64> for FakeUtcTime {
@@ -66,7 +114,7 @@ impl From<FakeUtcFields> for FakeUtcTime {
 
 #[cfg(test)]
 mod tests {
-    use super::FakeUtcTime;
+    use super::{FakeUtcTime, Period};
     use chrono::{Datelike, Timelike};
 
     #[test]
@@ -88,4 +136,39 @@ mod tests {
         let fake_utc_time2 = FakeUtcTime::parse_str(&date_str).unwrap();
         assert_eq!(fake_utc_time, fake_utc_time2);
     }
+
+    #[test]
+    pub fn periods() {
+        let time = FakeUtcTime::parse_str("2024/02/29 13:45").unwrap();
+        let bounds = |period| {
+            let start = time.period_start(period).unwrap().format_str().unwrap();
+            let end = time.period_end(period).unwrap().format_str().unwrap();
+            (start, end)
+        };
+        let day = (
+            String::from("2024/02/29 00:00"),
+            String::from("2024/03/01 00:00"),
+        );
+        let week = (
+            String::from("2024/02/26 00:00"),
+            String::from("2024/03/04 00:00"),
+        );
+        let month = (
+            String::from("2024/02/01 00:00"),
+            String::from("2024/03/01 00:00"),
+        );
+        let year = (
+            String::from("2024/01/01 00:00"),
+            String::from("2025/01/01 00:00"),
+        );
+        assert_eq!(bounds(Period::Day), day);
+        assert_eq!(bounds(Period::Week), week);
+        assert_eq!(bounds(Period::Month), month);
+        assert_eq!(bounds(Period::Year), year);
+        assert_eq!(
+            time.add_days(1).unwrap().format_str().unwrap(),
+            "2024/03/01 13:45"
+        );
+        assert!(time.add_days(i64::MAX).is_err());
+    }
 }
@@ -0,0 +1,86 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const DEFAULT_CURRENCY: &str = "EUR";
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Currency {
+    code: [u8; 3],
+}
+
+impl Currency {
+    pub fn new(code: &str) -> Result<Self> {
+        let bytes = code.as_bytes();
+        if bytes.len() != 3 || !bytes.iter().all(u8::is_ascii_uppercase) {
+            return Err(Error::InvalidCurrency(code.to_string()));
+        }
+        Ok(Self {
+            code: [bytes[0], bytes[1], bytes[2]],
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ascii uppercase letters are accepted by the constructor
+        std::str::from_utf8(&self.code).unwrap_or(DEFAULT_CURRENCY)
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self.as_str() {
+            "EUR" => "€",
+            "USD" => "$",
+            "GBP" => "£",
+            "JPY" => "¥",
+            code => code,
+        }
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self { code: *b"EUR" }
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.as_str().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let code: String = Deserialize::deserialize(deserializer)?;
+        Currency::new(&code).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Currency;
+
+    #[test]
+    pub fn json_conversion() {
+        let json_string = r#""USD""#;
+        let currency: Currency = serde_json::from_str(json_string).unwrap();
+        assert_eq!(currency.as_str(), "USD");
+        assert_eq!(currency.symbol(), "$");
+        let parsed_json = serde_json::to_string(&currency).unwrap();
+        assert_eq!(parsed_json, json_string);
+
+        assert!(serde_json::from_str::<Currency>(r#""usd""#).is_err());
+        assert!(Currency::new("EURO").is_err());
+        assert_eq!(Currency::default(), Currency::new("EUR").unwrap());
+    }
+}
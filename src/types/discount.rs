@@ -0,0 +1,119 @@
+use crate::{
+    error::{Error, Result},
+    types::money::Money,
+};
+use serde::{Deserialize, Serialize};
+
+pub const PERCENT_SCALE: i64 = 10_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", try_from = "DiscountJson")]
+pub enum Discount {
+    /// hundredths of a percent, so 1250 is 12.5%, at most 100%
+    Percent(u32),
+    /// never negative
+    Fixed(Money),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum DiscountJson {
+    Percent(u32),
+    Fixed(Money),
+}
+
+impl TryFrom<DiscountJson> for Discount {
+    type Error = Error;
+
+    fn try_from(value: DiscountJson) -> Result<Self> {
+        match value {
+            DiscountJson::Percent(percent) => Discount::percent(percent),
+            DiscountJson::Fixed(amount) => Discount::fixed(amount),
+        }
+    }
+}
+
+impl Discount {
+    pub fn percent(percent: u32) -> Result<Self> {
+        Discount::Percent(percent).checked()
+    }
+
+    pub fn fixed(amount: Money) -> Result<Self> {
+        Discount::Fixed(amount).checked()
+    }
+
+    pub fn checked(self) -> Result<Self> {
+        let valid = match self {
+            Discount::Percent(percent) => i64::from(percent) <= PERCENT_SCALE,
+            Discount::Fixed(amount) => !amount.is_negative(),
+        };
+        valid.then_some(self).ok_or(Error::InvalidDiscount(self))
+    }
+
+    /// a fixed discount larger than a positive price is refused, so that
+    /// discounts never turn a price negative
+    pub fn amount(&self, price: Money) -> Result<Money> {
+        match self.checked()? {
+            Discount::Percent(percent) => price.checked_scale(i64::from(percent), PERCENT_SCALE),
+            Discount::Fixed(amount) if !price.is_negative() && amount > price => {
+                Err(Error::InvalidDiscount(*self))
+            }
+            Discount::Fixed(amount) => Ok(amount),
+        }
+    }
+
+    pub fn apply(&self, price: Money) -> Result<Money> {
+        price.checked_sub(self.amount(price)?)
+    }
+}
+
+/// applies every discount in order, each one on the already discounted price
+pub fn apply_discounts(price: Money, discounts: &[Discount]) -> Result<Money> {
+    discounts
+        .iter()
+        .try_fold(price, |price, discount| discount.apply(price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Discount, apply_discounts};
+    use crate::types::money::Money;
+
+    #[test]
+    pub fn discount_order() {
+        let price = Money::new(1005);
+        let percent = Discount::Percent(1000);
+        let fixed = Discount::Fixed(Money::new(100));
+        // 10.05 - 1.01 (rounded) - 1.00
+        assert_eq!(
+            apply_discounts(price, &[percent, fixed]).unwrap(),
+            804.into()
+        );
+        // 10.05 - 1.00 - 0.91 (rounded)
+        assert_eq!(
+            apply_discounts(price, &[fixed, percent]).unwrap(),
+            814.into()
+        );
+        assert_eq!(
+            apply_discounts(Money::new(-1005), &[percent]).unwrap(),
+            (-904).into()
+        );
+
+        assert!(Discount::percent(10_001).is_err());
+        assert!(Discount::fixed(Money::new(-100)).is_err());
+        assert!(apply_discounts(Money::new(50), &[fixed]).is_err());
+        assert!(apply_discounts(price, &[Discount::Percent(20_000)]).is_err());
+    }
+
+    #[test]
+    pub fn json_conversion() {
+        let json_string = r#"[{"percent":1250},{"fixed":200}]"#;
+        let discounts: Vec<Discount> = serde_json::from_str(json_string).unwrap();
+        assert_eq!(
+            discounts,
+            vec![Discount::Percent(1250), Discount::Fixed(Money::new(200))]
+        );
+        assert_eq!(serde_json::to_string(&discounts).unwrap(), json_string);
+        assert!(serde_json::from_str::<Discount>(r#"{"percent":10001}"#).is_err());
+    }
+}
@@ -1,2 +1,6 @@
+pub mod currency;
+pub mod discount;
 pub mod internment;
 pub mod money;
+pub mod quantity;
+pub mod tax;
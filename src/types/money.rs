@@ -1,44 +1,177 @@
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    iter::Sum,
+    ops::{Add, Mul, Sub},
+};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default, Hash)]
 pub struct Money {
-    value: u32,
+    value: i64,
 }
 
 impl Money {
-    pub fn new(value_in_cents: u32) -> Self {
+    pub fn new(value_in_cents: i64) -> Self {
         Self {
             value: value_in_cents,
         }
     }
 
-    pub fn new_cents(value: u32, cents: u8) -> Self {
-        Self::new(value * 100 + cents as u32)
+    /// the inverse of `value_dec`, units and cents carry the same sign, so
+    /// -2.50 is `new_cents(-2, -50)` and -0.50 is `new_cents(0, -50)`
+    pub fn new_cents(units: i64, cents: i8) -> Result<Self> {
+        let mixed_signs = (units < 0 && cents > 0) || (units > 0 && cents < 0);
+        if mixed_signs || !(-99..=99).contains(&cents) {
+            return Err(Error::InvalidCents(units, cents));
+        }
+        units
+            .checked_mul(100)
+            .and_then(|value| value.checked_add(i64::from(cents)))
+            .map(Self::new)
+            .ok_or(Error::MoneyOverflow)
     }
 
-    pub fn cents(&self) -> u32 {
+    pub fn cents(&self) -> i64 {
         self.value
     }
 
-    pub fn value_dec(&self) -> f64 {
-        self.value as f64
+    pub fn units(&self) -> i64 {
+        self.value / 100
+    }
+
+    pub fn cents_part(&self) -> u8 {
+        (self.value % 100).unsigned_abs() as u8
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value < 0
+    }
+
+    pub fn checked_abs(&self) -> Result<Money> {
+        self.value
+            .checked_abs()
+            .map(Money::new)
+            .ok_or(Error::MoneyOverflow)
+    }
+
+    /// whole units and cents, both carrying the sign, so -2.50 is `(-2, -50)`
+    /// and -0.50 is `(0, -50)`
+    pub fn value_dec(&self) -> (i64, i8) {
+        (self.value / 100, (self.value % 100) as i8)
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money> {
+        self.value
+            .checked_add(other.value)
+            .map(Money::new)
+            .ok_or(Error::MoneyOverflow)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money> {
+        self.value
+            .checked_sub(other.value)
+            .map(Money::new)
+            .ok_or(Error::MoneyOverflow)
+    }
+
+    pub fn checked_neg(self) -> Result<Money> {
+        self.value
+            .checked_neg()
+            .map(Money::new)
+            .ok_or(Error::MoneyOverflow)
+    }
+
+    pub fn checked_mul(self, quantity: u32) -> Result<Money> {
+        self.value
+            .checked_mul(i64::from(quantity))
+            .map(Money::new)
+            .ok_or(Error::MoneyOverflow)
+    }
+
+    /// multiplies by `numerator / denominator`, rounding half away from zero
+    pub fn checked_scale(self, numerator: i64, denominator: i64) -> Result<Money> {
+        if denominator == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        let scaled = i128::from(self.value) * i128::from(numerator);
+        let denominator = i128::from(denominator);
+        let half = denominator.abs() / 2;
+        let value = if (scaled < 0) != (denominator < 0) {
+            (scaled - half) / denominator
+        } else {
+            (scaled + half) / denominator
+        };
+        i64::try_from(value)
+            .map(Money::new)
+            .map_err(|_| Error::MoneyOverflow)
+    }
+
+    pub fn checked_sum<Iter: IntoIterator<Item = Money>>(iter: Iter) -> Result<Money> {
+        iter.into_iter()
+            .try_fold(Money::default(), |acc, elem| acc.checked_add(elem))
     }
 }
 
-impl From<u32> for Money {
-    fn from(value: u32) -> Self {
+impl From<i64> for Money {
+    fn from(value: i64) -> Self {
         Self::new(value)
     }
 }
 
+/// the operators are the checked variants, they return an error on overflow
+/// instead of panicking
+impl Add for Money {
+    type Output = Result<Money>;
+
+    fn add(self, other: Money) -> Result<Money> {
+        self.checked_add(other)
+    }
+}
+
+impl Sub for Money {
+    type Output = Result<Money>;
+
+    fn sub(self, other: Money) -> Result<Money> {
+        self.checked_sub(other)
+    }
+}
+
+impl Mul<u32> for Money {
+    type Output = Result<Money>;
+
+    fn mul(self, quantity: u32) -> Result<Money> {
+        self.checked_mul(quantity)
+    }
+}
+
+impl Sum<Money> for Result<Money> {
+    fn sum<Iter: Iterator<Item = Money>>(iter: Iter) -> Self {
+        Money::checked_sum(iter)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let units = self.units().unsigned_abs();
+        write!(f, "{sign}{units}.{:02}", self.cents_part())
+    }
+}
+
 impl Serialize for Money {
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
         self.value.serialize(serializer)
     }
 }
 
 impl<'de> Deserialize<'de> for Money {
-    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
         Ok(Money::new(Deserialize::deserialize(deserializer)?))
     }
 }
@@ -46,6 +179,7 @@ impl<'de> Deserialize<'de> for Money {
 #[cfg(test)]
 mod tests {
     use super::Money;
+    use crate::error::{Error, Result};
 
     #[test]
     pub fn json_conversion() {
@@ -55,4 +189,73 @@ mod tests {
         let custom_money2: Money = serde_json::from_str(&parsed_json).unwrap();
         assert_eq!(custom_money, custom_money2);
     }
+
+    #[test]
+    pub fn arithmetic() {
+        let money = Money::new_cents(12, 5).unwrap();
+        assert_eq!(money.value_dec(), (12, 5));
+        assert_eq!(money.to_string(), "12.05");
+        assert_eq!(
+            money.checked_add(Money::new(95)).unwrap(),
+            Money::new_cents(13, 0).unwrap()
+        );
+        assert_eq!(money.checked_mul(3).unwrap(), Money::new(3615));
+        let total = Money::checked_sum(vec![Money::new(1), Money::new(2)]).unwrap();
+        assert_eq!(total, Money::new(3));
+
+        assert!(Money::new(i64::MAX).checked_add(Money::new(1)).is_err());
+        assert!(Money::new(i64::MIN).checked_sub(Money::new(1)).is_err());
+        assert!(Money::new(i64::MAX / 2).checked_mul(3).is_err());
+        assert!(Money::checked_sum(vec![Money::new(i64::MAX), Money::new(1)]).is_err());
+
+        assert_eq!(Money::new(1005).checked_scale(1, 2).unwrap(), 503.into());
+        assert_eq!(
+            Money::new(-1005).checked_scale(1, 2).unwrap(),
+            (-503).into()
+        );
+        assert!(Money::new(i64::MAX).checked_scale(2, 1).is_err());
+        assert!(Money::new(1005).checked_scale(1, 0).is_err());
+    }
+
+    #[test]
+    pub fn operators() {
+        let money = Money::new(1205);
+        assert_eq!((money + Money::new(95)).unwrap(), Money::new(1300));
+        assert_eq!((money - Money::new(1300)).unwrap(), Money::new(-95));
+        assert_eq!((money * 3).unwrap(), Money::new(3615));
+        let total: Result<Money> = [Money::new(1), Money::new(2)].into_iter().sum();
+        assert_eq!(total.unwrap(), Money::new(3));
+
+        assert!(matches!(
+            Money::new(i64::MAX) + Money::new(1),
+            Err(Error::MoneyOverflow)
+        ));
+        assert!((Money::new(i64::MIN) - Money::new(1)).is_err());
+        assert!((Money::new(i64::MAX / 2) * 3).is_err());
+        let total: Result<Money> = [Money::new(i64::MAX), Money::new(1)].into_iter().sum();
+        assert!(total.is_err());
+    }
+
+    #[test]
+    pub fn negative_values() {
+        let refund = Money::new_cents(-2, -50).unwrap();
+        assert_eq!(Money::new_cents(0, -50).unwrap().to_string(), "-0.50");
+        assert!(Money::new_cents(-2, 50).is_err());
+        assert!(Money::new_cents(i64::MAX, 0).is_err());
+        assert_eq!(refund.cents(), -250);
+        assert_eq!(refund.to_string(), "-2.50");
+        assert_eq!(Money::new(-5).to_string(), "-0.05");
+        assert_eq!(refund.value_dec(), (-2, -50));
+        assert_eq!(refund.checked_neg().unwrap(), Money::new(250));
+        assert!(Money::new(i64::MIN).checked_neg().is_err());
+        assert_eq!(refund.checked_abs().unwrap(), Money::new(250));
+        assert!(Money::new(i64::MIN).checked_abs().is_err());
+        assert_eq!(
+            Money::new(100).checked_add(refund).unwrap(),
+            Money::new(-150)
+        );
+        assert_eq!(refund.checked_mul(2).unwrap(), Money::new(-500));
+        let refund2: Money = serde_json::from_str("-250").unwrap();
+        assert_eq!(refund, refund2);
+    }
 }
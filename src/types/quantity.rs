@@ -0,0 +1,204 @@
+use crate::{
+    error::{Error, Result},
+    types::money::Money,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const MILLI_SCALE: u64 = 1_000;
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default, Serialize, Deserialize,
+)]
+pub enum Unit {
+    #[default]
+    #[serde(rename = "pcs")]
+    Pieces,
+    #[serde(rename = "kg")]
+    Kilograms,
+    #[serde(rename = "g")]
+    Grams,
+    #[serde(rename = "l")]
+    Liters,
+    #[serde(rename = "ml")]
+    Milliliters,
+}
+
+/// an amount in thousandths of `unit`, so 0.735 kg is 735 milli-kg
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub struct Quantity {
+    milli: u64,
+    unit: Unit,
+}
+
+impl Unit {
+    /// the unit prices are compared in, and how many of `self` make one of it
+    pub fn base(&self) -> (Unit, u64) {
+        match self {
+            Unit::Grams => (Unit::Kilograms, 1_000),
+            Unit::Milliliters => (Unit::Liters, 1_000),
+            unit => (*unit, 1),
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Pieces => "pcs",
+            Unit::Kilograms => "kg",
+            Unit::Grams => "g",
+            Unit::Liters => "l",
+            Unit::Milliliters => "ml",
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl Quantity {
+    pub fn new(milli: u64, unit: Unit) -> Self {
+        Self { milli, unit }
+    }
+
+    pub fn pieces(count: u32) -> Self {
+        Self::new(u64::from(count) * MILLI_SCALE, Unit::Pieces)
+    }
+
+    /// parses a decimal amount with up to three fractional digits, like
+    /// "0.735"
+    pub fn parse_str(amount: &str, unit: Unit) -> Result<Self> {
+        let invalid = || Error::InvalidQuantity(amount.to_string());
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || fraction.len() > 3 || !all_digits(whole) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+        let whole: u64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: u64 = format!("{fraction:0<3}").parse().map_err(|_| invalid())?;
+        let milli = whole
+            .checked_mul(MILLI_SCALE)
+            .and_then(|milli| milli.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Self::new(milli, unit))
+    }
+
+    pub fn milli(&self) -> u64 {
+        self.milli
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// price of this quantity at `unit_price` per unit, rounded half away
+    /// from zero to the cent
+    pub fn price(&self, unit_price: Money) -> Result<Money> {
+        let milli = i64::try_from(self.milli).map_err(|_| Error::MoneyOverflow)?;
+        unit_price.checked_scale(milli, MILLI_SCALE as i64)
+    }
+
+    /// the same amount expressed in the base unit, e.g. 735 g is 0.735 kg,
+    /// rounded half up to the thousandth
+    pub fn to_base(&self) -> (u64, Unit) {
+        let (unit, factor) = self.unit.base();
+        let round_up = self.milli % factor * 2 >= factor;
+        (self.milli / factor + u64::from(round_up), unit)
+    }
+}
+
+impl From<u32> for Quantity {
+    fn from(count: u32) -> Self {
+        Self::pieces(count)
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (whole, fraction) = (self.milli / MILLI_SCALE, self.milli % MILLI_SCALE);
+        if fraction == 0 {
+            write!(f, "{whole} {}", self.unit)
+        } else {
+            let fraction = format!("{fraction:03}");
+            write!(
+                f,
+                "{whole}.{} {}",
+                fraction.trim_end_matches('0'),
+                self.unit
+            )
+        }
+    }
+}
+
+/// serde for milli amounts written as plain JSON numbers, whole amounts stay
+/// integers so older files keep their format
+pub mod milli_number {
+    use super::{MILLI_SCALE, Quantity, Unit};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(milli: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        if milli.is_multiple_of(MILLI_SCALE) {
+            serializer.serialize_u64(milli / MILLI_SCALE)
+        } else {
+            serializer.serialize_f64(*milli as f64 / MILLI_SCALE as f64)
+        }
+    }
+
+    /// parses the decimal text of the number like [`Quantity::parse_str`],
+    /// so more than three decimals are refused instead of rounded
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let value = serde_json::Number::deserialize(deserializer)?;
+        Quantity::parse_str(&value.to_string(), Unit::Pieces)
+            .map(|quantity| quantity.milli())
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quantity, Unit, milli_number};
+    use crate::{error::Error, types::money::Money};
+
+    #[test]
+    pub fn quantity_price() {
+        let cheese = Quantity::parse_str("0.735", Unit::Kilograms).unwrap();
+        assert_eq!(cheese.milli(), 735);
+        assert_eq!(cheese.to_string(), "0.735 kg");
+        // 0.735 * 18.90 = 13.8915
+        assert_eq!(cheese.price(Money::new(1890)).unwrap(), 1389.into());
+        assert_eq!(
+            Quantity::from(3).price(Money::new(150)).unwrap(),
+            450.into()
+        );
+        assert_eq!(
+            Quantity::parse_str("250", Unit::Grams).unwrap().to_base(),
+            (250, Unit::Kilograms)
+        );
+        // 0.5 g is 0.0005 kg, which rounds up to the thousandth
+        assert_eq!(
+            Quantity::parse_str("0.5", Unit::Grams).unwrap().to_base(),
+            (1, Unit::Kilograms)
+        );
+        assert_eq!(
+            Quantity::parse_str("0.499", Unit::Grams).unwrap().to_base(),
+            (0, Unit::Kilograms)
+        );
+        assert!(Quantity::parse_str("1.2345", Unit::Liters).is_err());
+        assert!(Quantity::parse_str("-1", Unit::Liters).is_err());
+    }
+
+    #[test]
+    pub fn milli_number_json() {
+        let deserialize =
+            |json| milli_number::deserialize(&mut serde_json::Deserializer::from_str(json));
+        assert_eq!(deserialize("2").unwrap(), 2000);
+        assert_eq!(deserialize("0.735").unwrap(), 735);
+        let error = deserialize("0.7351").unwrap_err().to_string();
+        let expected = Error::InvalidQuantity("0.7351".into()).to_string();
+        assert!(error.starts_with(&expected), "{error}");
+        assert!(deserialize("-1").is_err());
+        assert!(deserialize("18446744073709552").is_err());
+    }
+}
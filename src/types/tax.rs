@@ -0,0 +1,105 @@
+use crate::{
+    error::Result,
+    types::{discount::PERCENT_SCALE, money::Money},
+};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaxMode {
+    /// prices already contain the tax
+    Inclusive,
+    /// the tax is added on top of the prices
+    Exclusive,
+}
+
+#[derive(
+    Getters, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize,
+)]
+pub struct Tax {
+    /// hundredths of a percent, so 2200 is 22%
+    rate: u32,
+    mode: TaxMode,
+}
+
+#[derive(Getters, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct TaxTotals {
+    net: Money,
+    tax: Money,
+    gross: Money,
+}
+
+impl Tax {
+    pub fn new(rate: u32, mode: TaxMode) -> Self {
+        Self { rate, mode }
+    }
+
+    /// price the buyer pays for a line priced `price`
+    pub fn gross(&self, price: Money) -> Result<Money> {
+        match self.mode {
+            TaxMode::Inclusive => Ok(price),
+            TaxMode::Exclusive => {
+                price.checked_add(price.checked_scale(i64::from(self.rate), PERCENT_SCALE)?)
+            }
+        }
+    }
+
+    /// splits a tax-inclusive amount into its net and tax portions
+    pub fn split(&self, gross: Money) -> Result<TaxTotals> {
+        let net = gross.checked_scale(PERCENT_SCALE, PERCENT_SCALE + i64::from(self.rate))?;
+        TaxTotals::new(net, gross.checked_sub(net)?)
+    }
+}
+
+impl TaxTotals {
+    pub fn new(net: Money, tax: Money) -> Result<Self> {
+        Ok(Self {
+            net,
+            tax,
+            gross: net.checked_add(tax)?,
+        })
+    }
+
+    pub fn untaxed(gross: Money) -> Self {
+        Self {
+            net: gross,
+            tax: Money::default(),
+            gross,
+        }
+    }
+
+    pub fn checked_add(self, other: TaxTotals) -> Result<TaxTotals> {
+        TaxTotals::new(
+            self.net.checked_add(other.net)?,
+            self.tax.checked_add(other.tax)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tax, TaxMode};
+    use crate::types::money::Money;
+
+    #[test]
+    pub fn tax_modes() {
+        let inclusive = Tax::new(2200, TaxMode::Inclusive);
+        let exclusive = Tax::new(2200, TaxMode::Exclusive);
+        assert_eq!(inclusive.gross(Money::new(1000)).unwrap(), 1000.into());
+        assert_eq!(exclusive.gross(Money::new(1000)).unwrap(), 1220.into());
+
+        let totals = inclusive.split(Money::new(1220)).unwrap();
+        assert_eq!(totals.net(), &Money::new(1000));
+        assert_eq!(totals.tax(), &Money::new(220));
+        assert_eq!(totals.gross(), &Money::new(1220));
+    }
+
+    #[test]
+    pub fn json_conversion() {
+        let json_string = r#"{"rate":1000,"mode":"exclusive"}"#;
+        let tax: Tax = serde_json::from_str(json_string).unwrap();
+        assert_eq!(tax, Tax::new(1000, TaxMode::Exclusive));
+        assert_eq!(serde_json::to_string(&tax).unwrap(), json_string);
+    }
+}